@@ -0,0 +1,128 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use app::{PublicKey, public_key_to_string, string_to_public_key};
+
+#[derive(Debug)]
+pub enum AddressBookError {
+    ReadError(io::Error),
+    WriteError(io::Error),
+    ParseError(toml::de::Error),
+    SerializeError(toml::ser::Error),
+    /// The public key of the named contact is invalid
+    InvalidPublicKey(String),
+}
+
+impl fmt::Display for AddressBookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddressBookError::ReadError(e) => write!(f, "failed to read address book: {}", e),
+            AddressBookError::WriteError(e) => write!(f, "failed to write address book: {}", e),
+            AddressBookError::ParseError(e) => write!(f, "invalid address book: {}", e),
+            AddressBookError::SerializeError(e) => write!(f, "failed to serialize address book: {}", e),
+            AddressBookError::InvalidPublicKey(name) => 
+                write!(f, "invalid public key for contact {:?} in address book", name),
+        }
+    }
+}
+
+impl Error for AddressBookError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AddressBookError::ReadError(e) | AddressBookError::WriteError(e) => Some(e),
+            AddressBookError::ParseError(e) => Some(e),
+            AddressBookError::SerializeError(e) => Some(e),
+            AddressBookError::InvalidPublicKey(_) => None,
+        }
+    }
+}
+
+/// A named remote destination that is not necessarily a direct friend
+#[derive(Debug, Clone)]
+pub struct Contact {
+    pub name: String,
+    pub public_key: PublicKey,
+}
+
+/// A contact, as stored in the address book file
+#[derive(Serialize, Deserialize)]
+struct ContactEntry {
+    name: String,
+    public_key: String,
+}
+
+/// The address book, as stored in a file.
+///
+/// The file is in TOML format. Public keys are encoded the same way as in stctrl's other outputs.
+///
+/// Example:
+/// ```text
+/// [[contacts]]
+/// name = "bob"
+/// public_key = "..."
+/// ```
+#[derive(Serialize, Deserialize, Default)]
+struct AddressBookFile {
+    #[serde(default)]
+    contacts: Vec<ContactEntry>,
+}
+
+/// Find a contact by its name
+pub fn contact_by_name<'a>(contacts: &'a [Contact], name: &str) -> Option<&'a Contact> {
+    contacts
+        .iter()
+        .find(|contact| contact.name == name)
+}
+
+/// Load all contacts from an address book file.
+/// A missing file is treated as an empty address book.
+pub fn load_address_book(path: &Path) -> Result<Vec<Contact>, AddressBookError> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(AddressBookError::ReadError(e)),
+    };
+
+    let address_book_file: AddressBookFile = toml::from_str(&data)
+        .map_err(AddressBookError::ParseError)?;
+
+    let mut contacts = Vec::new();
+    for contact_entry in address_book_file.contacts {
+        let public_key = match string_to_public_key(&contact_entry.public_key) {
+            Ok(public_key) => public_key,
+            Err(_) => return Err(AddressBookError::InvalidPublicKey(contact_entry.name)),
+        };
+        contacts.push(Contact {
+            name: contact_entry.name,
+            public_key,
+        });
+    }
+    Ok(contacts)
+}
+
+/// Store all contacts into an address book file, replacing its previous content
+pub fn store_address_book(contacts: &[Contact], path: &Path) -> Result<(), AddressBookError> {
+    let address_book_file = AddressBookFile {
+        contacts: contacts
+            .iter()
+            .map(|contact| ContactEntry {
+                name: contact.name.clone(),
+                public_key: public_key_to_string(&contact.public_key),
+            })
+            .collect(),
+    };
+
+    let data = toml::to_string(&address_book_file)
+        .map_err(AddressBookError::SerializeError)?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(AddressBookError::WriteError)?;
+    }
+
+    fs::write(path, data)
+        .map_err(AddressBookError::WriteError)
+}
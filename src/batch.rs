@@ -0,0 +1,133 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum BatchFileError {
+    ReadError(io::Error),
+    WriteError(io::Error),
+    ParseJsonError(serde_json::Error),
+    SerializeJsonError(serde_json::Error),
+    /// Reading, parsing or writing a CSV file
+    CsvError(csv::Error),
+}
+
+impl fmt::Display for BatchFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BatchFileError::ReadError(e) => write!(f, "failed to read file: {}", e),
+            BatchFileError::WriteError(e) => write!(f, "failed to write file: {}", e),
+            BatchFileError::ParseJsonError(e) => write!(f, "invalid JSON: {}", e),
+            BatchFileError::SerializeJsonError(e) => write!(f, "failed to serialize JSON: {}", e),
+            BatchFileError::CsvError(e) => write!(f, "CSV error: {}", e),
+        }
+    }
+}
+
+impl Error for BatchFileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BatchFileError::ReadError(e) | BatchFileError::WriteError(e) => Some(e),
+            BatchFileError::ParseJsonError(e) | BatchFileError::SerializeJsonError(e) => Some(e),
+            BatchFileError::CsvError(e) => Some(e),
+        }
+    }
+}
+
+/// A single payment of a batch file.
+///
+/// The destination is tried, in order, as a public key, the name of a friend, 
+/// a path to a ticket file and the name of a contact in the address book
+/// (See `resolve_destination` in funds.rs). It is resolved by the caller, 
+/// as resolving friend names requires a node report.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchPayment {
+    pub destination: String,
+    /// Amount of credits, as a decimal string
+    pub amount: String,
+    #[serde(default)]
+    pub invoice_id: Option<String>,
+    #[serde(default)]
+    pub memo: Option<String>,
+}
+
+/// The outcome of a single payment of a batch
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchResult {
+    pub destination: String,
+    pub amount: String,
+    pub memo: Option<String>,
+    /// "success" or "failure". In a dry run, "planned" or "failure".
+    pub status: String,
+    /// Paid fees. In a dry run, the planned fees.
+    pub fees: Option<String>,
+    pub receipt: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Is this path a JSON file? (Otherwise CSV is assumed)
+fn is_json_path(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension == "json")
+        .unwrap_or(false)
+}
+
+/// Load a batch of payments from a file.
+///
+/// Files with a `.json` extension should contain an array of payments.
+/// Any other file is read as CSV with a header line, for example:
+/// ```text
+/// destination,amount,invoice_id,memo
+/// alice,100,,March salary
+/// <public key>,250,<invoice id>,
+/// ```
+pub fn load_batch_from_file(path: &Path) -> Result<Vec<BatchPayment>, BatchFileError> {
+    if is_json_path(path) {
+        let data = fs::read_to_string(path)
+            .map_err(BatchFileError::ReadError)?;
+        return serde_json::from_str(&data)
+            .map_err(BatchFileError::ParseJsonError);
+    }
+
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .map_err(BatchFileError::CsvError)?;
+
+    let mut batch_payments = Vec::new();
+    for record in reader.deserialize() {
+        let mut batch_payment: BatchPayment = record
+            .map_err(BatchFileError::CsvError)?;
+        // Empty optional fields are read as empty strings:
+        batch_payment.invoice_id = batch_payment.invoice_id
+            .filter(|invoice_id| !invoice_id.is_empty());
+        batch_payment.memo = batch_payment.memo
+            .filter(|memo| !memo.is_empty());
+        batch_payments.push(batch_payment);
+    }
+    Ok(batch_payments)
+}
+
+/// Store the results of a batch of payments into a file.
+/// The format (JSON or CSV) is determined by the file extension, as in `load_batch_from_file`.
+pub fn store_batch_results_to_file(batch_results: &[BatchResult],
+                                   path: &Path) -> Result<(), BatchFileError> {
+    if is_json_path(path) {
+        let data = serde_json::to_string_pretty(batch_results)
+            .map_err(BatchFileError::SerializeJsonError)?;
+        return fs::write(path, data)
+            .map_err(BatchFileError::WriteError);
+    }
+
+    let file = File::create(path)
+        .map_err(BatchFileError::WriteError)?;
+    let mut writer = csv::Writer::from_writer(file);
+    for batch_result in batch_results {
+        writer.serialize(batch_result)
+            .map_err(BatchFileError::CsvError)?;
+    }
+    writer.flush()
+        .map_err(BatchFileError::WriteError)
+}
@@ -8,42 +8,207 @@
     warnings
 )]
 
-#[macro_use] extern crate log;
-
 // use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+use std::io;
 use std::path::PathBuf;
 use std::env;
+use std::process;
+use std::time::Duration;
 
 use log::Level;
 
-use futures::executor::ThreadPool;
+use clap::ArgMatches;
 
-use clap::{Arg, App, AppSettings, SubCommand /*, ArgMatches */};
+use futures::executor::ThreadPool;
 
 use stctrl::info::{info, InfoError};
 use stctrl::config::{config, ConfigError};
 use stctrl::funds::{funds, FundsError};
+use stctrl::shell::{shell, ShellError};
+use stctrl::cli::build_app;
+use stctrl::node::AppError;
+use stctrl::profile::{load_profile, ProfileError};
+use stctrl::timeout::{with_timeout, Timer, TimeoutNode};
 
 use app::{connect, identity_from_file, load_node_from_file};
 
 
 const STCTRL_ID_FILE: &str = "STCTRL_ID_FILE";
 const STCTRL_NODE_TICKET_FILE: &str = "STCTRL_NODE_TICKET_FILE";
-
+const STCTRL_PROFILE: &str = "STCTRL_PROFILE";
+
+// Exit codes. Documented in the help message (See `EXIT_CODES_HELP` in cli.rs).
+
+/// Any error not covered by the other exit codes
+const EXIT_FAILURE: i32 = 1;
+/// Invalid command line arguments, unknown names or invalid input files
+const EXIT_USAGE: i32 = 2;
+/// Failed to connect to the node, the connection was lost or an operation timed out
+const EXIT_CONNECTION: i32 = 3;
+/// The application lacks the permissions required for the command
+const EXIT_PERMISSIONS: i32 = 4;
+/// A payment was not completed
+const EXIT_PAYMENT: i32 = 5;
+/// Failed to read or write local files (Data directory, output files)
+const EXIT_STORAGE: i32 = 6;
 
 #[derive(Debug)]
 enum StCtrlError {
-    CreateThreadPoolError,
+    CreateThreadPoolError(io::Error),
+    ProfileError(ProfileError),
     MissingIdFileArgument,
-    IdFileDoesNotExist,
+    IdFileDoesNotExist(PathBuf),
     MissingNodeTicketArgument,
-    NodeTicketFileDoesNotExist,
-    InvalidNodeTicketFile,
-    SpawnIdentityServiceError,
-    ConnectionError,
+    NodeTicketFileDoesNotExist(PathBuf),
+    InvalidNodeTicketFile(PathBuf, AppError),
+    SpawnIdentityServiceError(PathBuf, AppError),
+    ConnectionError(AppError),
+    ParseTimeoutError(String),
+    ConnectTimeout(Duration),
+    OperationTimeout(Duration, Box<StCtrlError>),
     InfoError(InfoError),
     ConfigError(ConfigError),
     FundsError(FundsError),
+    ShellError(ShellError),
+}
+
+impl fmt::Display for StCtrlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StCtrlError::CreateThreadPoolError(e) => write!(f, "failed to create a thread pool: {}", e),
+            StCtrlError::ProfileError(e) => write!(f, "{}", e),
+            StCtrlError::MissingIdFileArgument => 
+                write!(f, "no identity file. Use --idfile, set {} or use a profile", STCTRL_ID_FILE),
+            StCtrlError::IdFileDoesNotExist(path) => 
+                write!(f, "identity file not found: {}", path.display()),
+            StCtrlError::MissingNodeTicketArgument => 
+                write!(f, "no node ticket. Use --ticket, set {} or use a profile", STCTRL_NODE_TICKET_FILE),
+            StCtrlError::NodeTicketFileDoesNotExist(path) => 
+                write!(f, "node ticket file not found: {}", path.display()),
+            StCtrlError::InvalidNodeTicketFile(path, e) => 
+                write!(f, "invalid node ticket file {}: {}", path.display(), e),
+            StCtrlError::SpawnIdentityServiceError(path, e) => 
+                write!(f, "failed to load identity from {}: {}", path.display(), e),
+            StCtrlError::ConnectionError(e) => write!(f, "failed to connect to the node: {}", e),
+            StCtrlError::ParseTimeoutError(timeout) => 
+                write!(f, "invalid timeout {:?}: expected a number of seconds", timeout),
+            StCtrlError::ConnectTimeout(timeout) => 
+                write!(f, "connection to the node timed out after {} seconds", timeout.as_secs()),
+            StCtrlError::OperationTimeout(timeout, e) => 
+                write!(f, "{} (the node did not respond within {} seconds)", e, timeout.as_secs()),
+            StCtrlError::InfoError(e) => write!(f, "{}", e),
+            StCtrlError::ConfigError(e) => write!(f, "{}", e),
+            StCtrlError::FundsError(e) => write!(f, "{}", e),
+            StCtrlError::ShellError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for StCtrlError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StCtrlError::CreateThreadPoolError(e) => Some(e),
+            StCtrlError::InvalidNodeTicketFile(_, e)
+                | StCtrlError::SpawnIdentityServiceError(_, e)
+                | StCtrlError::ConnectionError(e) => Some(e),
+            StCtrlError::ProfileError(e) => Some(e),
+            StCtrlError::OperationTimeout(_, e) => Some(e.as_ref()),
+            StCtrlError::InfoError(e) => Some(e),
+            StCtrlError::ConfigError(e) => Some(e),
+            StCtrlError::FundsError(e) => Some(e),
+            StCtrlError::ShellError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl StCtrlError {
+    /// The process exit code for this error
+    fn exit_code(&self) -> i32 {
+        match self {
+            StCtrlError::CreateThreadPoolError(_) => EXIT_FAILURE,
+            StCtrlError::ProfileError(_) 
+                | StCtrlError::MissingIdFileArgument
+                | StCtrlError::IdFileDoesNotExist(_)
+                | StCtrlError::MissingNodeTicketArgument
+                | StCtrlError::NodeTicketFileDoesNotExist(_)
+                | StCtrlError::InvalidNodeTicketFile(..)
+                | StCtrlError::SpawnIdentityServiceError(..)
+                | StCtrlError::ParseTimeoutError(_) => EXIT_USAGE,
+            StCtrlError::ConnectionError(_)
+                | StCtrlError::ConnectTimeout(_)
+                | StCtrlError::OperationTimeout(..) => EXIT_CONNECTION,
+            StCtrlError::InfoError(e) => info_exit_code(e),
+            StCtrlError::ConfigError(e) => config_exit_code(e),
+            StCtrlError::FundsError(e) => funds_exit_code(e),
+            StCtrlError::ShellError(ShellError::GetReportError(_)) => EXIT_CONNECTION,
+            StCtrlError::ShellError(ShellError::ApplyMutationError(_)) => EXIT_FAILURE,
+            StCtrlError::ShellError(ShellError::ReadLineError(_)) => EXIT_FAILURE,
+        }
+    }
+}
+
+fn info_exit_code(e: &InfoError) -> i32 {
+    match e {
+        InfoError::GetReportError(_) 
+            | InfoError::Timeout(_)
+            | InfoError::ReportMutationsClosed => EXIT_CONNECTION,
+        InfoError::FriendNameNotFound(_)
+            | InfoError::NoLastIncomingMoveToken(_) => EXIT_USAGE,
+        InfoError::OutputFileAlreadyExists(_)
+            | InfoError::StoreNodeToFileError(..)
+            | InfoError::OutputError(_)
+            | InfoError::SerializeLastFriendTokenError(_)
+            | InfoError::StoreLastFriendTokenError(_) => EXIT_STORAGE,
+        InfoError::BalanceOverflow
+            | InfoError::ApplyMutationError(_) => EXIT_FAILURE,
+    }
+}
+
+fn config_exit_code(e: &ConfigError) -> i32 {
+    match e {
+        ConfigError::NoPermissions => EXIT_PERMISSIONS,
+        ConfigError::GetReportError(_)
+            | ConfigError::Timeout(_) => EXIT_CONNECTION,
+        ConfigError::AppConfigError(..) => EXIT_FAILURE,
+        ConfigError::WriteTempTicketError(_)
+            | ConfigError::OutputFileAlreadyExists(_)
+            | ConfigError::StoreTicketError(_)
+            | ConfigError::StoreNodeConfigFileError(_) => EXIT_STORAGE,
+        _ => EXIT_USAGE,
+    }
+}
+
+fn funds_exit_code(e: &FundsError) -> i32 {
+    match e {
+        FundsError::GetReportError(_)
+            | FundsError::Timeout(_) => EXIT_CONNECTION,
+        FundsError::NoFundsPermissions
+            | FundsError::NoRoutesPermissions => EXIT_PERMISSIONS,
+        FundsError::AppRoutesError(_)
+            | FundsError::SendFundsError(_)
+            | FundsError::NoSuitableRoute
+            | FundsError::ReceiptAckError(_)
+            | FundsError::MaxFeeExceeded
+            | FundsError::FeesOverflow
+            | FundsError::PaymentNotConfirmed
+            | FundsError::BatchPaymentsFailed(_)
+            | FundsError::AttemptsFailed(_)
+            | FundsError::PaymentPartFailed(..) => EXIT_PAYMENT,
+        FundsError::OutputFileAlreadyExists(_)
+            | FundsError::StoreInvoiceError(_)
+            | FundsError::NoDataDir
+            | FundsError::ReadConfirmationError(_)
+            | FundsError::OutputError(_)
+            | FundsError::StoreBatchResultsError(_)
+            | FundsError::StoreLedgerError(_)
+            | FundsError::LoadLedgerError(_)
+            | FundsError::LoadAddressBookError(_)
+            | FundsError::StoreAddressBookError(_) => EXIT_STORAGE,
+        _ => EXIT_USAGE,
+    }
 }
 
 
@@ -65,6 +230,12 @@ impl From<FundsError> for StCtrlError {
     }
 }
 
+impl From<ShellError> for StCtrlError {
+    fn from(e: ShellError) -> Self {
+        StCtrlError::ShellError(e)
+    }
+}
+
 /// Get environment variable
 fn get_env(key: &str) -> Option<String> {
     for (cur_key, value) in env::vars() {
@@ -85,304 +256,118 @@ fn env_stctrl_node_ticket_file() -> Option<PathBuf> {
     Some(PathBuf::from(get_env(STCTRL_NODE_TICKET_FILE)?))
 }
 
+/// Get a timeout from the command line, falling back to the profile's setting
+fn get_timeout(matches: &ArgMatches, arg_name: &str, opt_profile_timeout: Option<u64>)
+    -> Result<Option<Duration>, StCtrlError> {
+
+    let opt_secs = match matches.value_of(arg_name) {
+        Some(timeout_str) => Some(timeout_str.parse::<u64>()
+            .map_err(|_| StCtrlError::ParseTimeoutError(timeout_str.to_owned()))?),
+        None => opt_profile_timeout,
+    };
+    Ok(opt_secs.map(Duration::from_secs))
+}
 
 fn run() -> Result<(), StCtrlError> {
 
     simple_logger::init_with_level(Level::Warn).unwrap();
     let mut thread_pool = ThreadPool::new()
-        .map_err(|_| StCtrlError::CreateThreadPoolError)?;
-
-    let matches = App::new("stctrl: offST ConTRoL")
-                          // TOOD: Does this setting work for recursive subcommands?
-                          .setting(AppSettings::SubcommandRequiredElseHelp)
-                          .version("0.1.0")
-                          .author("real <real@freedomlayer.org>")
-                          .about("A command line client for offst node")
-                          // STCTRL_ID_FILE
-                          .arg(Arg::with_name("idfile")
-                               .short("I")
-                               .long("idfile")
-                               .value_name("idfile")
-                               .help("Client identity file path")
-                               .required(false))
-                          // STCTRL_NODE_TICKET_FILE
-                          .arg(Arg::with_name("node_ticket")
-                               .short("T")
-                               .long("ticket")
-                               .value_name("node_ticket")
-                               .help("Node ticket file path")
-                               .required(false))
-
-                          /* ------------[Info] ------------- */
-                          .subcommand(SubCommand::with_name("info")
-                              .about("show offst node information")
-                              .subcommand(SubCommand::with_name("relays")
-                                  .about("Show all configured relays"))
-
-                              .subcommand(SubCommand::with_name("index")
-                                  .about("Show all configured index servers"))
-
-                              .subcommand(SubCommand::with_name("friends")
-                                  .about("Show all configured friends"))
-
-                              .subcommand(SubCommand::with_name("last-friend-token")
-                                  .about("Last received token from this friend")
-                                  .arg(Arg::with_name("friend_name")
-                                       .short("n")
-                                       .long("name")
-                                       .value_name("friend_name")
-                                       .help("friend name")
-                                       .required(true)))
-
-                              .subcommand(SubCommand::with_name("balance")
-                                  .about("Display current balance"))
-
-                              .subcommand(SubCommand::with_name("export-ticket")
-                                  .about("Export a ticket of this node's contact information")
-                                  .arg(Arg::with_name("output_file")
-                                       .short("o")
-                                       .long("output")
-                                       .value_name("output_file")
-                                       .help("output node ticket file path")
-                                       .required(true))))
-
-                          /* ------------[Config] ------------- */
-                          .subcommand(SubCommand::with_name("config")
-                              .about("configure offst node")
-                              .subcommand(SubCommand::with_name("add-relay")
-                                  .about("Add a relay")
-                                  .arg(Arg::with_name("relay_file")
-                                       .short("r")
-                                       .long("relay")
-                                       .value_name("relay_file")
-                                       .help("relay file")
-                                       .required(true))
-                                  .arg(Arg::with_name("relay_name")
-                                       .short("n")
-                                       .long("name")
-                                       .value_name("relay_name")
-                                       .help("relay name")
-                                       .required(true)))
-
-                              .subcommand(SubCommand::with_name("remove-relay")
-                                  .about("Remove a relay")
-                                  .arg(Arg::with_name("relay_name")
-                                       .short("n")
-                                       .long("name")
-                                       .value_name("relay_name")
-                                       .help("relay name")
-                                       .required(true)))
-
-                              .subcommand(SubCommand::with_name("add-index")
-                                  .about("Add an index server")
-                                  .arg(Arg::with_name("index_file")
-                                       .short("x")
-                                       .long("index")
-                                       .value_name("index_file")
-                                       .help("index file")
-                                       .required(true))
-                                  .arg(Arg::with_name("index_name")
-                                       .short("n")
-                                       .long("name")
-                                       .value_name("index_name")
-                                       .help("Index server name")
-                                       .required(true)))
-
-                              .subcommand(SubCommand::with_name("remove-index")
-                                  .about("Remove an index server")
-                                  .arg(Arg::with_name("index_name")
-                                       .short("n")
-                                       .long("name")
-                                       .value_name("index_name")
-                                       .help("Index server name")
-                                       .required(true)))
-
-                              .subcommand(SubCommand::with_name("add-friend")
-                                  .about("Add a friend")
-                                  .arg(Arg::with_name("friend_file")
-                                       .short("f")
-                                       .long("friend")
-                                       .value_name("friend_file")
-                                       .help("friend file")
-                                       .required(true))
-                                  .arg(Arg::with_name("friend_name")
-                                       .short("n")
-                                       .long("name")
-                                       .value_name("friend_name")
-                                       .help("friend name")
-                                       .required(true))
-                                  .arg(Arg::with_name("friend_balance")
-                                       .short("b")
-                                       .long("balance")
-                                       .value_name("friend_balance")
-                                       .help("Initial balance with friend")
-                                       .required(true)))
-
-                              .subcommand(SubCommand::with_name("set-friend-relays")
-                                  .about("Set a friend's relays")
-                                  .arg(Arg::with_name("friend_file")
-                                       .short("f")
-                                       .long("friend")
-                                       .value_name("friend_file")
-                                       .help("friend file")
-                                       .required(true))
-                                  .arg(Arg::with_name("friend_name")
-                                       .short("n")
-                                       .long("name")
-                                       .value_name("friend_name")
-                                       .help("friend name")
-                                       .required(true)))
-
-                              .subcommand(SubCommand::with_name("remove-friend")
-                                  .about("Remove a friend\
-                                          Caution: This is a violent operation.")
-                                  .arg(Arg::with_name("friend_name")
-                                       .short("n")
-                                       .long("name")
-                                       .value_name("friend_name")
-                                       .help("friend's name")
-                                       .required(true)))
-
-                              .subcommand(SubCommand::with_name("enable-friend")
-                                  .about("Enable a friend")
-                                  .arg(Arg::with_name("friend_name")
-                                       .short("n")
-                                       .long("name")
-                                       .value_name("friend_name")
-                                       .help("friend's name")
-                                       .required(true)))
-
-                              .subcommand(SubCommand::with_name("disable-friend")
-                                  .about("Disable a friend")
-                                  .arg(Arg::with_name("friend_name")
-                                       .short("n")
-                                       .long("name")
-                                       .value_name("friend_name")
-                                       .help("friend's name")
-                                       .required(true)))
-
-                              .subcommand(SubCommand::with_name("open-friend")
-                                  .about("Open a friend")
-                                  .arg(Arg::with_name("friend_name")
-                                       .short("n")
-                                       .long("name")
-                                       .value_name("friend_name")
-                                       .help("friend's name")
-                                       .required(true)))
-
-                              .subcommand(SubCommand::with_name("close-friend")
-                                  .about("Close a friend")
-                                  .arg(Arg::with_name("friend_name")
-                                       .short("n")
-                                       .long("name")
-                                       .value_name("friend_name")
-                                       .help("friend's name")
-                                       .required(true)))
-
-                              .subcommand(SubCommand::with_name("set-friend-max-debt")
-                                  .about("Set friend's max debt")
-                                  .arg(Arg::with_name("friend_name")
-                                       .short("n")
-                                       .long("name")
-                                       .value_name("friend_name")
-                                       .help("friend's name")
-                                       .required(true))
-                                  .arg(Arg::with_name("max_debt")
-                                       .short("m")
-                                       .long("mdebt")
-                                       .value_name("max_debt")
-                                       .help("Max debt value")
-                                       .required(true)))
-
-                              .subcommand(SubCommand::with_name("reset-friend")
-                                  .about("Reset mutual credit with friend according to the friend's terms")
-                                  .arg(Arg::with_name("friend_name")
-                                       .short("n")
-                                       .long("name")
-                                       .value_name("friend_name")
-                                       .help("friend's name")
-                                       .required(true))))
-
-                          /* ------------[Funds] ------------- */
-                          .subcommand(SubCommand::with_name("funds")
-                              .about("configure offst node")
-                              .subcommand(SubCommand::with_name("send")
-                                  .about("Send funds to a remote destination")
-                                  .arg(Arg::with_name("destination")
-                                       .short("d")
-                                       .long("destination")
-                                       .value_name("destination")
-                                       .help("recipient's public key")
-                                       .required(true))
-                                  .arg(Arg::with_name("amount")
-                                       .short("a")
-                                       .long("amount")
-                                       .value_name("amount")
-                                       .help("Amount of credits to send")
-                                       .required(true))))
-                          .get_matches();
+        .map_err(StCtrlError::CreateThreadPoolError)?;
+
+    let matches = match build_app().get_matches_safe() {
+        Ok(matches) => matches,
+        // Help and version messages are also reported as errors:
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) => {
+            eprintln!("{}", e.message);
+            process::exit(EXIT_USAGE);
+        },
+    };
+
+    // Load profile from the configuration file (If any):
+    let opt_profile_name = matches.value_of("profile")
+        .map(|profile_name| profile_name.to_owned())
+        .or_else(|| get_env(STCTRL_PROFILE));
+    let profile = load_profile(opt_profile_name.as_ref().map(|profile_name| profile_name.as_str()))
+        .map_err(StCtrlError::ProfileError)?
+        .unwrap_or_default();
+
+    let opt_connect_timeout = get_timeout(&matches, "connect_timeout", profile.connect_timeout)?;
+    let opt_timeout = get_timeout(&matches, "timeout", profile.timeout)?;
 
     // Get application's identity:
     let idfile_pathbuf = match matches.value_of("idfile") {
         Some(idfile) => PathBuf::from(idfile),
-        None => {
-            if let Some(idfile_pathbuf) = env_stctrl_id_file() {
-                idfile_pathbuf
-            } else {
-                return Err(StCtrlError::MissingIdFileArgument);
-            }
-        },
+        None => profile.setting_or_env(profile.idfile.clone(), env_stctrl_id_file())
+            .ok_or(StCtrlError::MissingIdFileArgument)?,
     };
 
     if !idfile_pathbuf.exists() {
-        return Err(StCtrlError::IdFileDoesNotExist);
+        return Err(StCtrlError::IdFileDoesNotExist(idfile_pathbuf));
     }
 
     // Get node's connection information (node-ticket):
     let node_ticket_pathbuf = match matches.value_of("node_ticket") {
         Some(node_ticket) => PathBuf::from(node_ticket),
-        None => {
-            if let Some(node_ticket_pathbuf) = env_stctrl_node_ticket_file() {
-                node_ticket_pathbuf
-            } else {
-                return Err(StCtrlError::MissingNodeTicketArgument);
-            }
-        },
+        None => profile.setting_or_env(profile.node_ticket.clone(), env_stctrl_node_ticket_file())
+            .ok_or(StCtrlError::MissingNodeTicketArgument)?,
     };
 
     if !node_ticket_pathbuf.exists() {
-        return Err(StCtrlError::NodeTicketFileDoesNotExist);
+        return Err(StCtrlError::NodeTicketFileDoesNotExist(node_ticket_pathbuf));
     }
 
     // Get node information from file:
     let node_address = load_node_from_file(&node_ticket_pathbuf)
-        .map_err(|_| StCtrlError::InvalidNodeTicketFile)?;
+        .map_err(|e| StCtrlError::InvalidNodeTicketFile(node_ticket_pathbuf.clone(), AppError::new(e)))?;
 
     // Spawn identity service:
     let app_identity_client = identity_from_file(&idfile_pathbuf, thread_pool.clone())
-        .map_err(|_| StCtrlError::SpawnIdentityServiceError)?;
+        .map_err(|e| StCtrlError::SpawnIdentityServiceError(idfile_pathbuf.clone(), AppError::new(e)))?;
+
 
+    // A single timer thread limits both connecting and node operations:
+    let timer = Timer::new();
 
     let c_thread_pool = thread_pool.clone();
     thread_pool.run(async move {
         // Connect to node:
-        let node_connection = await!(connect(node_address.public_key,
+        let connect_fut = Box::pin(connect(node_address.public_key,
                             node_address.address,
                             app_identity_client,
-                            c_thread_pool.clone()))
-            .map_err(|_| StCtrlError::ConnectionError)?;
-
-        Ok(match matches.subcommand() {
-            ("info", Some(matches)) => await!(info(matches, node_connection))?,
-            ("config", Some(matches)) => await!(config(matches, node_connection))?,
-            ("funds", Some(matches)) => await!(funds(matches, node_connection))?,
+                            c_thread_pool.clone()));
+        let connect_res = match opt_connect_timeout {
+            Some(connect_timeout) => await!(with_timeout(&timer, connect_fut, connect_timeout))
+                .map_err(|_| StCtrlError::ConnectTimeout(connect_timeout))?,
+            None => await!(connect_fut),
+        };
+        let mut node_connection = connect_res
+            .map_err(|e| StCtrlError::ConnectionError(AppError::new(e)))?;
+
+        let mut node = TimeoutNode::new(&mut node_connection, &timer, opt_timeout);
+        let res = match matches.subcommand() {
+            ("info", Some(matches)) => await!(info(matches, &profile, &mut node)).map_err(StCtrlError::from),
+            ("config", Some(matches)) => await!(config(matches, &mut node)).map_err(StCtrlError::from),
+            ("funds", Some(matches)) => await!(funds(matches, &profile, &mut node)).map_err(StCtrlError::from),
+            ("shell", Some(_)) => await!(shell(&profile, &mut node)).map_err(StCtrlError::from),
             _ => unreachable!(),
-        })
+        };
+
+        // A timeout may also surface as another error, for example a failed batch row
+        // or payment part. Errors of commands inside the shell are only printed:
+        match (res, opt_timeout) {
+            (Err(e), Some(timeout)) if node.timed_out()
+                && e.exit_code() != EXIT_CONNECTION
+                && matches.subcommand_name() != Some("shell") =>
+                Err(StCtrlError::OperationTimeout(timeout, Box::new(e))),
+            (res, _) => res,
+        }
     })
 }
 
 fn main() {
     if let Err(e) = run() {
-        error!("run() error: {:?}", e);
+        eprintln!("Error: {}", e);
+        process::exit(e.exit_code());
     }
 }
@@ -0,0 +1,593 @@
+use clap::{Arg, App, AppSettings, SubCommand};
+
+use crate::funds::ROUTE_STRATEGIES;
+use crate::output::OUTPUT_FORMATS;
+
+/// Exit codes of stctrl, shown at the end of the help message
+const EXIT_CODES_HELP: &str = "EXIT CODES:
+    0    Success
+    1    Any other error
+    2    Invalid arguments, unknown names or invalid input files
+    3    Failed to connect to the node, the connection was lost or an operation timed out
+    4    Missing permissions for the command
+    5    Payment was not completed
+    6    Failed to read or write local files";
+
+/// A command and its subcommands, as defined by `build_app`
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub subcommands: &'static [CommandSpec],
+}
+
+const fn command(name: &'static str, subcommands: &'static [CommandSpec]) -> CommandSpec {
+    CommandSpec { name, subcommands }
+}
+
+/// The subcommands of stctrl. Must be kept in sync with `build_app`.
+pub const COMMANDS: &[CommandSpec] = &[
+    command("info", &[
+        command("relays", &[]),
+        command("index", &[]),
+        command("friends", &[]),
+        command("friend", &[]),
+        command("last-friend-token", &[]),
+        command("balance", &[]),
+        command("watch", &[]),
+        command("export-ticket", &[]),
+    ]),
+    command("config", &[
+        command("add-relay", &[]),
+        command("remove-relay", &[]),
+        command("add-index", &[]),
+        command("remove-index", &[]),
+        command("add-friend", &[]),
+        command("set-friend-relays", &[]),
+        command("remove-friend", &[]),
+        command("enable-friend", &[]),
+        command("disable-friend", &[]),
+        command("open-friend", &[]),
+        command("close-friend", &[]),
+        command("set-friend-max-debt", &[]),
+        command("reset-friend", &[]),
+        command("apply", &[]),
+        command("export", &[]),
+    ]),
+    command("funds", &[
+        command("send", &[]),
+        command("send-batch", &[]),
+        command("routes", &[]),
+        command("history", &[]),
+        command("contacts", &[
+            command("add", &[]),
+            command("remove", &[]),
+            command("list", &[]),
+        ]),
+        command("create-invoice", &[]),
+    ]),
+    command("shell", &[]),
+];
+
+/// Build stctrl's command line interface
+pub fn build_app<'a, 'b>() -> App<'a, 'b> {
+    build_app_with(true)
+}
+
+/// Build the command line interface used for commands inside the shell.
+/// The arguments for connecting to the node are left out, 
+/// as the shell is already connected.
+pub fn build_shell_app<'a, 'b>() -> App<'a, 'b> {
+    build_app_with(false)
+}
+
+fn build_app_with<'a, 'b>(connection_args: bool) -> App<'a, 'b> {
+    let app = App::new("stctrl: offST ConTRoL")
+            // TOOD: Does this setting work for recursive subcommands?
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .version("0.1.0")
+            .author("real <real@freedomlayer.org>")
+            .about("A command line client for offst node")
+            .after_help(EXIT_CODES_HELP);
+
+    let app = if connection_args {
+        add_connection_args(app)
+    } else {
+        app
+    };
+
+    app
+            .arg(Arg::with_name("format")
+                 .long("format")
+                 .value_name("format")
+                 .possible_values(OUTPUT_FORMATS)
+                 .help("Output format (default: table)")
+                 .global(true)
+                 .required(false))
+            .arg(Arg::with_name("data_dir")
+                 .long("data-dir")
+                 .value_name("data_dir")
+                 .help("Local data directory (default: ~/.stctrl)")
+                 .global(true)
+                 .required(false))
+            .arg(Arg::with_name("dry_run")
+                 .long("dry-run")
+                 .help("Only show the operations that would be performed and the files that would be written")
+                 .global(true)
+                 .required(false))
+
+            /* ------------[Info] ------------- */
+            .subcommand(SubCommand::with_name("info")
+                .about("show offst node information")
+                .subcommand(SubCommand::with_name("relays")
+                    .about("Show all configured relays"))
+
+                .subcommand(SubCommand::with_name("index")
+                    .about("Show all configured index servers and the index client's connection state")
+                    .arg(Arg::with_name("watch")
+                         .short("w")
+                         .long("watch")
+                         .help("Continuously display the connection state and switches between index servers")))
+
+                .subcommand(SubCommand::with_name("friends")
+                    .about("Show all configured friends"))
+
+                .subcommand(SubCommand::with_name("friend")
+                    .about("Show detailed information about a single friend")
+                    .arg(Arg::with_name("friend_name")
+                         .short("n")
+                         .long("name")
+                         .value_name("friend_name")
+                         .help("friend name")
+                         .required(true)))
+
+                .subcommand(SubCommand::with_name("last-friend-token")
+                    .about("Export last received token from this friend")
+                    .arg(Arg::with_name("friend_name")
+                         .short("n")
+                         .long("name")
+                         .value_name("friend_name")
+                         .help("friend name")
+                         .required(true))
+                    .arg(Arg::with_name("output_file")
+                         .short("o")
+                         .long("output")
+                         .value_name("output_file")
+                         .help("output last token file path")
+                         .required(true)))
+
+                .subcommand(SubCommand::with_name("balance")
+                    .about("Display current balance"))
+
+                .subcommand(SubCommand::with_name("watch")
+                    .about("Continuously display friends, balances and index server connection"))
+
+                .subcommand(SubCommand::with_name("export-ticket")
+                    .about("Export a ticket of this node's contact information")
+                    .arg(Arg::with_name("output_file")
+                         .short("o")
+                         .long("output")
+                         .value_name("output_file")
+                         .help("output node ticket file path")
+                         .required(true))))
+
+            /* ------------[Config] ------------- */
+            .subcommand(SubCommand::with_name("config")
+                .about("configure offst node")
+                .subcommand(SubCommand::with_name("add-relay")
+                    .about("Add a relay")
+                    .arg(Arg::with_name("relay_file")
+                         .short("r")
+                         .long("relay")
+                         .value_name("relay_file")
+                         .help("relay file")
+                         .required(true))
+                    .arg(Arg::with_name("relay_name")
+                         .short("n")
+                         .long("name")
+                         .value_name("relay_name")
+                         .help("relay name")
+                         .required(true)))
+
+                .subcommand(SubCommand::with_name("remove-relay")
+                    .about("Remove a relay")
+                    .arg(Arg::with_name("relay_name")
+                         .short("n")
+                         .long("name")
+                         .value_name("relay_name")
+                         .help("relay name")
+                         .required(true)))
+
+                .subcommand(SubCommand::with_name("add-index")
+                    .about("Add an index server")
+                    .arg(Arg::with_name("index_file")
+                         .short("x")
+                         .long("index")
+                         .value_name("index_file")
+                         .help("index file")
+                         .required(true))
+                    .arg(Arg::with_name("index_name")
+                         .short("n")
+                         .long("name")
+                         .value_name("index_name")
+                         .help("Index server name")
+                         .required(true)))
+
+                .subcommand(SubCommand::with_name("remove-index")
+                    .about("Remove an index server")
+                    .arg(Arg::with_name("index_name")
+                         .short("n")
+                         .long("name")
+                         .value_name("index_name")
+                         .help("Index server name")
+                         .required(true)))
+
+                .subcommand(SubCommand::with_name("add-friend")
+                    .about("Add a friend")
+                    .arg(Arg::with_name("friend_file")
+                         .short("f")
+                         .long("friend")
+                         .value_name("friend_file")
+                         .help("friend file")
+                         .required(true))
+                    .arg(Arg::with_name("friend_name")
+                         .short("n")
+                         .long("name")
+                         .value_name("friend_name")
+                         .help("friend name")
+                         .required(true))
+                    .arg(Arg::with_name("friend_balance")
+                         .short("b")
+                         .long("balance")
+                         .value_name("friend_balance")
+                         .help("Initial balance with friend")
+                         .required(true)))
+
+                .subcommand(SubCommand::with_name("set-friend-relays")
+                    .about("Set a friend's relays")
+                    .arg(Arg::with_name("friend_file")
+                         .short("f")
+                         .long("friend")
+                         .value_name("friend_file")
+                         .help("friend file")
+                         .required(true))
+                    .arg(Arg::with_name("friend_name")
+                         .short("n")
+                         .long("name")
+                         .value_name("friend_name")
+                         .help("friend name")
+                         .required(true)))
+
+                .subcommand(SubCommand::with_name("remove-friend")
+                    .about("Remove a friend\
+                            Caution: This is a violent operation.")
+                    .arg(Arg::with_name("friend_name")
+                         .short("n")
+                         .long("name")
+                         .value_name("friend_name")
+                         .help("friend's name")
+                         .required(true)))
+
+                .subcommand(SubCommand::with_name("enable-friend")
+                    .about("Enable a friend")
+                    .arg(Arg::with_name("friend_name")
+                         .short("n")
+                         .long("name")
+                         .value_name("friend_name")
+                         .help("friend's name")
+                         .required(true)))
+
+                .subcommand(SubCommand::with_name("disable-friend")
+                    .about("Disable a friend")
+                    .arg(Arg::with_name("friend_name")
+                         .short("n")
+                         .long("name")
+                         .value_name("friend_name")
+                         .help("friend's name")
+                         .required(true)))
+
+                .subcommand(SubCommand::with_name("open-friend")
+                    .about("Open a friend")
+                    .arg(Arg::with_name("friend_name")
+                         .short("n")
+                         .long("name")
+                         .value_name("friend_name")
+                         .help("friend's name")
+                         .required(true)))
+
+                .subcommand(SubCommand::with_name("close-friend")
+                    .about("Close a friend")
+                    .arg(Arg::with_name("friend_name")
+                         .short("n")
+                         .long("name")
+                         .value_name("friend_name")
+                         .help("friend's name")
+                         .required(true)))
+
+                .subcommand(SubCommand::with_name("set-friend-max-debt")
+                    .about("Set friend's max debt")
+                    .arg(Arg::with_name("friend_name")
+                         .short("n")
+                         .long("name")
+                         .value_name("friend_name")
+                         .help("friend's name")
+                         .required(true))
+                    .arg(Arg::with_name("max_debt")
+                         .short("m")
+                         .long("mdebt")
+                         .value_name("max_debt")
+                         .help("Max debt value")
+                         .required(true)))
+
+                .subcommand(SubCommand::with_name("reset-friend")
+                    .about("Reset mutual credit with friend according to the friend's terms")
+                    .arg(Arg::with_name("friend_name")
+                         .short("n")
+                         .long("name")
+                         .value_name("friend_name")
+                         .help("friend's name")
+                         .required(true)))
+
+                .subcommand(SubCommand::with_name("apply")
+                    .about("Bring the node to the configuration described in a file")
+                    .arg(Arg::with_name("node_config_file")
+                         .value_name("node_config_file")
+                         .help("node configuration file (TOML or JSON)")
+                         .required(true))
+                    .arg(Arg::with_name("prune")
+                         .long("prune")
+                         .help("Remove friends that are not listed in the file. \
+                                Removing a friend destroys its mutual credit channel")))
+
+                .subcommand(SubCommand::with_name("export")
+                    .about("Export a snapshot of the node configuration, to be used with apply")
+                    .arg(Arg::with_name("output_file")
+                         .short("o")
+                         .long("output")
+                         .value_name("output_file")
+                         .help("output node configuration file path (TOML or JSON)")
+                         .required(true))))
+
+            /* ------------[Funds] ------------- */
+            .subcommand(SubCommand::with_name("funds")
+                .about("configure offst node")
+                .subcommand(SubCommand::with_name("send")
+                    .about("Send funds to a remote destination")
+                    .arg(Arg::with_name("destination")
+                         .short("d")
+                         .long("destination")
+                         .value_name("destination")
+                         .help("recipient's public key, friend name, contact name or ticket file")
+                         .required_unless("invoice_file")
+                         .conflicts_with("invoice_file"))
+                    .arg(Arg::with_name("amount")
+                         .short("a")
+                         .long("amount")
+                         .value_name("amount")
+                         .help("Amount of credits to send")
+                         .required_unless("invoice_file")
+                         .conflicts_with("invoice_file"))
+                    .arg(Arg::with_name("invoice_file")
+                         .short("i")
+                         .long("invoice")
+                         .value_name("invoice_file")
+                         .help("Pay an invoice file")
+                         .required(false))
+                    .arg(Arg::with_name("receipt_out")
+                         .long("receipt-out")
+                         .value_name("receipt_out")
+                         .help("Additionally store the payment receipt in this file")
+                         .required(false))
+                    .arg(Arg::with_name("max_fee")
+                         .long("max-fee")
+                         .value_name("max_fee")
+                         .help("Maximal amount of credits to pay as fees")
+                         .required(false))
+                    .arg(Arg::with_name("confirm")
+                         .long("confirm")
+                         .help("Show route, amount and fees and ask for confirmation before paying")
+                         .required(false))
+                    .arg(Arg::with_name("attempts")
+                         .long("attempts")
+                         .value_name("attempts")
+                         .help("Maximal amount of payment attempts. \
+                                Every attempt uses a different route (default: 1)")
+                         .required(false))
+                    .arg(Arg::with_name("deadline")
+                         .long("deadline")
+                         .value_name("seconds")
+                         .help("Do not start new payment attempts after this amount of seconds")
+                         .required(false))
+                    .arg(Arg::with_name("split")
+                         .long("split")
+                         .help("Split the payment between multiple routes if required")
+                         .conflicts_with("receipt_out")
+                         .conflicts_with("attempts")
+                         .conflicts_with("route_strategy")
+                         .required(false))
+                    .arg(Arg::with_name("route_strategy")
+                         .long("route-strategy")
+                         .value_name("route_strategy")
+                         .possible_values(ROUTE_STRATEGIES)
+                         .help("Route selection strategy (default: cheapest)")
+                         .required(false))
+                    .arg(Arg::with_name("avoid_friend")
+                         .long("avoid-friend")
+                         .value_name("friend_name")
+                         .help("Do not route the payment through this friend")
+                         .multiple(true)
+                         .number_of_values(1)
+                         .required(false)))
+
+                .subcommand(SubCommand::with_name("send-batch")
+                    .about("Send funds to multiple destinations, one after the other")
+                    .arg(Arg::with_name("batch_file")
+                         .short("f")
+                         .long("file")
+                         .value_name("batch_file")
+                         .help("Payments file (CSV or JSON). \
+                                Columns: destination (public key, friend name or contact name), amount, \
+                                invoice_id (optional), memo (optional)")
+                         .required(true))
+                    .arg(Arg::with_name("output_file")
+                         .short("o")
+                         .long("output")
+                         .value_name("output_file")
+                         .help("output results file path (CSV or JSON)")
+                         .required(true))
+                    .arg(Arg::with_name("max_fee")
+                         .long("max-fee")
+                         .value_name("max_fee")
+                         .help("Maximal amount of credits to pay as fees for every payment")
+                         .required(false))
+                    .arg(Arg::with_name("attempts")
+                         .long("attempts")
+                         .value_name("attempts")
+                         .help("Maximal amount of attempts for every payment (default: 1)")
+                         .required(false))
+                    .arg(Arg::with_name("route_strategy")
+                         .long("route-strategy")
+                         .value_name("route_strategy")
+                         .possible_values(ROUTE_STRATEGIES)
+                         .help("Route selection strategy (default: cheapest)")
+                         .required(false))
+                    .arg(Arg::with_name("avoid_friend")
+                         .long("avoid-friend")
+                         .value_name("friend_name")
+                         .help("Do not route the payments through this friend")
+                         .multiple(true)
+                         .number_of_values(1)
+                         .required(false)))
+
+                .subcommand(SubCommand::with_name("routes")
+                    .about("Show the routes suggested by the index server, without paying")
+                    .arg(Arg::with_name("destination")
+                         .short("d")
+                         .long("destination")
+                         .value_name("destination")
+                         .help("recipient's public key, friend name, contact name or ticket file")
+                         .required(true))
+                    .arg(Arg::with_name("amount")
+                         .short("a")
+                         .long("amount")
+                         .value_name("amount")
+                         .help("Amount of credits to send")
+                         .required(true)))
+
+                .subcommand(SubCommand::with_name("history")
+                    .about("Show payments recorded in the local ledger")
+                    .arg(Arg::with_name("since")
+                         .long("since")
+                         .value_name("date")
+                         .help("Show payments from this date (YYYY-MM-DD or RFC 3339)")
+                         .required(false))
+                    .arg(Arg::with_name("until")
+                         .long("until")
+                         .value_name("date")
+                         .help("Show payments before this date, exclusive (YYYY-MM-DD or RFC 3339)")
+                         .required(false))
+                    .arg(Arg::with_name("destination")
+                         .short("d")
+                         .long("destination")
+                         .value_name("destination")
+                         .help("Show payments to this destination (public key, friend name, contact name or ticket file)")
+                         .required(false))
+                    .arg(Arg::with_name("status")
+                         .long("status")
+                         .value_name("status")
+                         .possible_values(&["success", "failure"])
+                         .help("Show only payments with this status")
+                         .required(false)))
+
+                .subcommand(SubCommand::with_name("contacts")
+                    .about("Manage the local address book of named destinations")
+                    .subcommand(SubCommand::with_name("add")
+                        .about("Add a contact")
+                        .arg(Arg::with_name("contact_name")
+                             .short("n")
+                             .long("name")
+                             .value_name("contact_name")
+                             .help("Name of the contact")
+                             .required(true))
+                        .arg(Arg::with_name("destination")
+                             .short("d")
+                             .long("destination")
+                             .value_name("destination")
+                             .help("Contact's public key or ticket file")
+                             .required(true)))
+                    .subcommand(SubCommand::with_name("remove")
+                        .about("Remove a contact")
+                        .arg(Arg::with_name("contact_name")
+                             .short("n")
+                             .long("name")
+                             .value_name("contact_name")
+                             .help("Name of the contact")
+                             .required(true)))
+                    .subcommand(SubCommand::with_name("list")
+                        .about("Show all contacts")))
+
+                .subcommand(SubCommand::with_name("create-invoice")
+                    .about("Create an invoice file, to be paid using send --invoice")
+                    .arg(Arg::with_name("amount")
+                         .short("a")
+                         .long("amount")
+                         .value_name("amount")
+                         .help("Amount of credits to request")
+                         .required(true))
+                    .arg(Arg::with_name("destination")
+                         .short("d")
+                         .long("destination")
+                         .value_name("destination")
+                         .help("recipient's public key, friend name, contact name or ticket file (default: this node)")
+                         .required(false))
+                    .arg(Arg::with_name("memo")
+                         .short("m")
+                         .long("memo")
+                         .value_name("memo")
+                         .help("A note describing the payment")
+                         .required(false))
+                    .arg(Arg::with_name("output_file")
+                         .short("o")
+                         .long("output")
+                         .value_name("output_file")
+                         .help("output invoice file path")
+                         .required(true))))
+
+            /* ------------[Shell] ------------- */
+            .subcommand(SubCommand::with_name("shell")
+                .about("Connect once and run info, config and funds commands interactively"))
+}
+
+/// Arguments for connecting to the node
+fn add_connection_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app
+            // STCTRL_ID_FILE
+            .arg(Arg::with_name("idfile")
+                 .short("I")
+                 .long("idfile")
+                 .value_name("idfile")
+                 .help("Client identity file path")
+                 .required(false))
+            // STCTRL_NODE_TICKET_FILE
+            .arg(Arg::with_name("node_ticket")
+                 .short("T")
+                 .long("ticket")
+                 .value_name("node_ticket")
+                 .help("Node ticket file path")
+                 .required(false))
+            // STCTRL_PROFILE
+            .arg(Arg::with_name("profile")
+                 .short("P")
+                 .long("profile")
+                 .value_name("profile")
+                 .help("Profile name in the configuration file (~/.config/stctrl/config.toml)")
+                 .required(false))
+            .arg(Arg::with_name("connect_timeout")
+                 .long("connect-timeout")
+                 .value_name("seconds")
+                 .help("Maximal amount of seconds to wait for a connection to the node")
+                 .required(false))
+            .arg(Arg::with_name("timeout")
+                 .long("timeout")
+                 .value_name("seconds")
+                 .help("Maximal amount of seconds to wait for each operation on the node")
+                 .required(false))
+}
@@ -1,170 +1,389 @@
-use std::path::PathBuf;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use clap::ArgMatches;
 
-use app::{NodeConnection, AppConfig, 
-    NamedRelayAddress, NamedIndexServerAddress, 
+use tempdir::TempDir;
+
+use app::{NamedRelayAddress, NamedIndexServerAddress, 
+    RelayAddress, IndexServerAddress, FriendAddress,
+    public_key_to_string,
     load_relay_from_file, load_index_server_from_file,
-    load_friend_from_file, PublicKey};
-use app::report::{NodeReport, ChannelStatusReport};
+    load_friend_from_file, store_relay_to_file,
+    store_index_server_to_file, store_friend_to_file, PublicKey};
+use app::report::{NodeReport, ChannelStatusReport, 
+    FriendStatusReport, RequestsStatusReport, ResetTermsReport};
+
+use crate::node::{Node, NodeError, AppError, NodeConfig};
 
 #[derive(Debug)]
 pub enum ConfigError {
     /// No permissions to configure node
     NoPermissions,
-    GetReportError,
-    RelayNameAlreadyExists,
-    RelayFileNotFound,
-    LoadRelayFromFileError,
-    AppConfigError,
-    RelayNameNotFound,
-    IndexNameAlreadyExists,
-    IndexFileNotFound,
-    LoadIndexFromFileError,
-    FriendNameAlreadyExists,
-    ParseBalanceError,
-    FriendFileNotFound,
-    LoadFriendFromFileError,
-    FriendPublicKeyMismatch,
-    FriendNameNotFound,
-    ParseMaxDebtError,
-    ChannelNotInconsistent,
-    UnknownRemoteResetTerms,
-}
-
-async fn config_add_relay<'a>(matches: &'a ArgMatches<'a>, 
-                              mut app_config: AppConfig,
-                              node_report: NodeReport) -> Result<(), ConfigError> {
+    GetReportError(NodeError),
+    /// The node did not respond in time
+    Timeout(Duration),
+    RelayNameAlreadyExists(String),
+    RelayFileNotFound(PathBuf),
+    LoadRelayFromFileError(PathBuf, AppError),
+    /// The node failed to apply a configuration operation (Described by the string)
+    AppConfigError(String, NodeError),
+    RelayNameNotFound(String),
+    IndexNameAlreadyExists(String),
+    IndexNameNotFound(String),
+    IndexFileNotFound(PathBuf),
+    LoadIndexFromFileError(PathBuf, AppError),
+    FriendNameAlreadyExists(String),
+    ParseBalanceError(String),
+    FriendFileNotFound(PathBuf),
+    LoadFriendFromFileError(PathBuf, AppError),
+    FriendPublicKeyMismatch(String),
+    FriendNameNotFound(String),
+    ParseMaxDebtError(String),
+    ChannelNotInconsistent(String),
+    UnknownRemoteResetTerms(String),
+    NodeConfigFileNotFound(PathBuf),
+    ReadNodeConfigFileError(io::Error),
+    ParseNodeConfigFileError(Box<dyn Error + Send + Sync>),
+    DuplicateName(String),
+    DuplicatePublicKey(String),
+    FriendNameMismatch(String),
+    MissingTicket(String),
+    WriteTempTicketError(io::Error),
+    OutputFileAlreadyExists(PathBuf),
+    StoreTicketError(AppError),
+    StoreNodeConfigFileError(Box<dyn Error + Send + Sync>),
+}
 
-    let relay_file = matches.value_of("relay_file").unwrap();
-    let relay_name = matches.value_of("relay_name").unwrap();
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::NoPermissions => 
+                write!(f, "the application has no permissions to configure the node"),
+            ConfigError::GetReportError(e) => write!(f, "failed to obtain a report from the node: {}", e),
+            ConfigError::Timeout(timeout) => 
+                write!(f, "the node did not respond within {} seconds", timeout.as_secs()),
+            ConfigError::RelayNameAlreadyExists(name) => 
+                write!(f, "a relay named {:?} already exists", name),
+            ConfigError::RelayFileNotFound(path) => 
+                write!(f, "relay file not found: {}", path.display()),
+            ConfigError::LoadRelayFromFileError(path, e) => 
+                write!(f, "invalid relay file {}: {}", path.display(), e),
+            ConfigError::AppConfigError(description, e) => 
+                write!(f, "the node failed to {}: {}", description, e),
+            ConfigError::RelayNameNotFound(name) => write!(f, "no relay named {:?}", name),
+            ConfigError::IndexNameAlreadyExists(name) => 
+                write!(f, "an index server named {:?} already exists", name),
+            ConfigError::IndexNameNotFound(name) => write!(f, "no index server named {:?}", name),
+            ConfigError::IndexFileNotFound(path) => 
+                write!(f, "index server file not found: {}", path.display()),
+            ConfigError::LoadIndexFromFileError(path, e) => 
+                write!(f, "invalid index server file {}: {}", path.display(), e),
+            ConfigError::FriendNameAlreadyExists(name) => 
+                write!(f, "a friend named {:?} already exists", name),
+            ConfigError::ParseBalanceError(balance_str) => 
+                write!(f, "invalid balance: {:?}", balance_str),
+            ConfigError::FriendFileNotFound(path) => 
+                write!(f, "friend file not found: {}", path.display()),
+            ConfigError::LoadFriendFromFileError(path, e) => 
+                write!(f, "invalid friend file {}: {}", path.display(), e),
+            ConfigError::FriendPublicKeyMismatch(name) => 
+                write!(f, "the friend file does not belong to the friend named {:?}", name),
+            ConfigError::FriendNameNotFound(name) => write!(f, "no friend named {:?}", name),
+            ConfigError::ParseMaxDebtError(max_debt_str) => 
+                write!(f, "invalid max debt: {:?}", max_debt_str),
+            ConfigError::ChannelNotInconsistent(name) => 
+                write!(f, "the channel with {:?} is not inconsistent, there is nothing to reset", name),
+            ConfigError::UnknownRemoteResetTerms(name) => 
+                write!(f, "reset terms of {:?} were not received yet", name),
+            ConfigError::NodeConfigFileNotFound(path) => 
+                write!(f, "node configuration file not found: {}", path.display()),
+            ConfigError::ReadNodeConfigFileError(e) => 
+                write!(f, "failed to read node configuration file: {}", e),
+            ConfigError::ParseNodeConfigFileError(e) => 
+                write!(f, "invalid node configuration file: {}", e),
+            ConfigError::DuplicateName(name) => 
+                write!(f, "the name {:?} is used more than once", name),
+            ConfigError::DuplicatePublicKey(public_key) => 
+                write!(f, "the public key {} is used more than once", public_key),
+            ConfigError::FriendNameMismatch(name) => 
+                write!(f, "friend {:?} is already known by a different name. \
+                       Friends can not be renamed", name),
+            ConfigError::MissingTicket(name) => 
+                write!(f, "entry {:?} has neither ticket nor ticket_data", name),
+            ConfigError::WriteTempTicketError(e) => 
+                write!(f, "failed to access a temporary ticket file: {}", e),
+            ConfigError::OutputFileAlreadyExists(path) => 
+                write!(f, "output file already exists: {}", path.display()),
+            ConfigError::StoreTicketError(e) => write!(f, "failed to encode a ticket: {}", e),
+            ConfigError::StoreNodeConfigFileError(e) => 
+                write!(f, "failed to store node configuration file: {}", e),
+        }
+    }
+}
 
-    for named_relay_address in node_report.funder_report.relays {
-        if named_relay_address.name == relay_name {
-            return Err(ConfigError::RelayNameAlreadyExists);
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::GetReportError(e) 
+                | ConfigError::AppConfigError(_, e) => Some(e),
+            ConfigError::LoadRelayFromFileError(_, e) 
+                | ConfigError::LoadIndexFromFileError(_, e) 
+                | ConfigError::LoadFriendFromFileError(_, e) 
+                | ConfigError::StoreTicketError(e) => Some(e),
+            ConfigError::ReadNodeConfigFileError(e) 
+                | ConfigError::WriteTempTicketError(e) => Some(e),
+            ConfigError::ParseNodeConfigFileError(e) 
+                | ConfigError::StoreNodeConfigFileError(e) => Some(&**e),
+            _ => None,
         }
     }
+}
 
-    let relay_pathbuf = PathBuf::from(relay_file);
-    if !relay_pathbuf.exists() {
-        return Err(ConfigError::RelayFileNotFound);
+/// Convert a failed node operation using `to_error`. A timeout is reported on its own.
+fn node_error<F: FnOnce(NodeError) -> ConfigError>(e: NodeError, to_error: F) -> ConfigError {
+    match e {
+        NodeError::Timeout(timeout) => ConfigError::Timeout(timeout),
+        e => to_error(e),
     }
+}
 
-    let relay_address = load_relay_from_file(&relay_pathbuf)
-        .map_err(|_| ConfigError::LoadRelayFromFileError)?;
+/// Parameters for adding a relay
+#[derive(Debug, Clone)]
+pub struct AddRelayParams {
+    pub name: String,
+    pub relay_address: RelayAddress,
+}
+
+/// Parameters for removing a relay
+#[derive(Debug, Clone)]
+pub struct RemoveRelayParams {
+    pub name: String,
+}
+
+/// Parameters for adding an index server
+#[derive(Debug, Clone)]
+pub struct AddIndexParams {
+    pub name: String,
+    pub index_server_address: IndexServerAddress,
+}
+
+/// Parameters for removing an index server
+#[derive(Debug, Clone)]
+pub struct RemoveIndexParams {
+    pub name: String,
+}
+
+/// Parameters for adding a friend
+#[derive(Debug, Clone)]
+pub struct AddFriendParams {
+    pub name: String,
+    pub friend_address: FriendAddress,
+    /// Initial balance
+    pub balance: i128,
+}
+
+/// Parameters for updating the relays of a friend
+#[derive(Debug, Clone)]
+pub struct SetFriendRelaysParams {
+    pub name: String,
+    /// Must have the same public key as the friend
+    pub friend_address: FriendAddress,
+}
+
+/// Parameters for operations on a single friend 
+/// (remove, enable, disable, open, close and reset)
+#[derive(Debug, Clone)]
+pub struct FriendParams {
+    pub name: String,
+}
+
+/// Parameters for setting the remote max debt of a friend
+#[derive(Debug, Clone)]
+pub struct SetFriendMaxDebtParams {
+    pub name: String,
+    pub max_debt: u128,
+}
+
+/// Plan adding a relay
+pub fn plan_add_relay(params: AddRelayParams,
+                      node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
+
+    for named_relay_address in &node_report.funder_report.relays {
+        if named_relay_address.name == params.name {
+            return Err(ConfigError::RelayNameAlreadyExists(params.name));
+        }
+    }
 
     let named_relay_address = NamedRelayAddress {
-        public_key: relay_address.public_key,
-        address: relay_address.address,
-        name: relay_name.to_owned(),
+        public_key: params.relay_address.public_key,
+        address: params.relay_address.address,
+        name: params.name,
     };
 
-    await!(app_config.add_relay(named_relay_address))
-        .map_err(|_| ConfigError::AppConfigError)
+    Ok(ConfigOp::AddRelay(named_relay_address))
 }
 
-async fn config_remove_relay<'a>(matches: &'a ArgMatches<'a>, 
-                                 mut app_config: AppConfig,
-                                 node_report: NodeReport) -> Result<(), ConfigError> {
+fn config_add_relay<'a>(matches: &'a ArgMatches<'a>, 
+                        node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
 
+    let relay_file = matches.value_of("relay_file").unwrap();
     let relay_name = matches.value_of("relay_name").unwrap();
 
+    let relay_pathbuf = PathBuf::from(relay_file);
+    if !relay_pathbuf.exists() {
+        return Err(ConfigError::RelayFileNotFound(relay_pathbuf));
+    }
+
+    let relay_address = load_relay_from_file(&relay_pathbuf)
+        .map_err(|e| ConfigError::LoadRelayFromFileError(relay_pathbuf.clone(), AppError::new(e)))?;
+
+    plan_add_relay(AddRelayParams {
+        name: relay_name.to_owned(),
+        relay_address,
+    }, node_report)
+}
+
+/// Plan removing a relay
+pub fn plan_remove_relay(params: RemoveRelayParams,
+                         node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
+
     let mut opt_relay_public_key = None;
-    for named_relay_address in node_report.funder_report.relays {
-        if named_relay_address.name == relay_name {
+    for named_relay_address in &node_report.funder_report.relays {
+        if named_relay_address.name == params.name {
             opt_relay_public_key = Some(named_relay_address.public_key.clone());
         }
     }
 
-    let relay_public_key = opt_relay_public_key
-        .ok_or(ConfigError::RelayNameNotFound)?;
+    let relay_public_key = match opt_relay_public_key {
+        Some(relay_public_key) => relay_public_key,
+        None => return Err(ConfigError::RelayNameNotFound(params.name)),
+    };
 
-    await!(app_config.remove_relay(relay_public_key))
-        .map_err(|_| ConfigError::AppConfigError)
+    Ok(ConfigOp::RemoveRelay(params.name, relay_public_key))
 }
 
-async fn config_add_index<'a>(matches: &'a ArgMatches<'a>, 
-                              mut app_config: AppConfig,
-                              node_report: NodeReport) -> Result<(), ConfigError> {
+fn config_remove_relay<'a>(matches: &'a ArgMatches<'a>, 
+                           node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
 
-    let index_file = matches.value_of("index_file").unwrap();
-    let index_name = matches.value_of("index_name").unwrap();
+    let relay_name = matches.value_of("relay_name").unwrap();
+
+    plan_remove_relay(RemoveRelayParams {
+        name: relay_name.to_owned(),
+    }, node_report)
+}
+
+/// Plan adding an index server
+pub fn plan_add_index(params: AddIndexParams,
+                      node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
 
-    for named_index_server_address in node_report.index_client_report.index_servers {
-        if named_index_server_address.name == index_name {
-            return Err(ConfigError::IndexNameAlreadyExists);
+    for named_index_server_address in &node_report.index_client_report.index_servers {
+        if named_index_server_address.name == params.name {
+            return Err(ConfigError::IndexNameAlreadyExists(params.name));
         }
     }
 
+    let named_index_server_address = NamedIndexServerAddress {
+        public_key: params.index_server_address.public_key,
+        address: params.index_server_address.address,
+        name: params.name,
+    };
+
+    Ok(ConfigOp::AddIndexServer(named_index_server_address))
+}
+
+fn config_add_index<'a>(matches: &'a ArgMatches<'a>, 
+                        node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
+
+    let index_file = matches.value_of("index_file").unwrap();
+    let index_name = matches.value_of("index_name").unwrap();
+
     let index_pathbuf = PathBuf::from(index_file);
     if !index_pathbuf.exists() {
-        return Err(ConfigError::IndexFileNotFound);
+        return Err(ConfigError::IndexFileNotFound(index_pathbuf));
     }
 
     let index_server_address = load_index_server_from_file(&index_pathbuf)
-        .map_err(|_| ConfigError::LoadIndexFromFileError)?;
+        .map_err(|e| ConfigError::LoadIndexFromFileError(index_pathbuf.clone(), AppError::new(e)))?;
 
-    let named_index_server_address = NamedIndexServerAddress {
-        public_key: index_server_address.public_key,
-        address: index_server_address.address,
+    plan_add_index(AddIndexParams {
         name: index_name.to_owned(),
+        index_server_address,
+    }, node_report)
+}
+
+/// Plan removing an index server
+pub fn plan_remove_index(params: RemoveIndexParams,
+                         node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
+
+    let mut opt_index_public_key = None;
+    for named_index_server_address in &node_report.index_client_report.index_servers {
+        if named_index_server_address.name == params.name {
+            opt_index_public_key = Some(named_index_server_address.public_key.clone());
+        }
+    }
+
+    let index_public_key = match opt_index_public_key {
+        Some(index_public_key) => index_public_key,
+        None => return Err(ConfigError::IndexNameNotFound(params.name)),
     };
 
-    await!(app_config.add_index_server(named_index_server_address))
-        .map_err(|_| ConfigError::AppConfigError)
+    Ok(ConfigOp::RemoveIndexServer(params.name, index_public_key))
 }
 
-async fn config_remove_index<'a>(matches: &'a ArgMatches<'a>, 
-                                 mut app_config: AppConfig,
-                                 node_report: NodeReport) -> Result<(), ConfigError> {
+fn config_remove_index<'a>(matches: &'a ArgMatches<'a>, 
+                           node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
 
     let index_name = matches.value_of("index_name").unwrap();
 
-    let mut opt_index_public_key = None;
-    for named_index_server_address in node_report.index_client_report.index_servers {
-        if named_index_server_address.name == index_name {
-            opt_index_public_key = Some(named_index_server_address.public_key.clone());
+    plan_remove_index(RemoveIndexParams {
+        name: index_name.to_owned(),
+    }, node_report)
+}
+
+/// Plan adding a friend
+pub fn plan_add_friend(params: AddFriendParams,
+                       node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
+
+    for (_friend_public_key, friend_report) in &node_report.funder_report.friends {
+        if friend_report.name == params.name {
+            return Err(ConfigError::FriendNameAlreadyExists(params.name));
         }
     }
 
-    let index_public_key = opt_index_public_key
-        .ok_or(ConfigError::RelayNameNotFound)?;
-
-    await!(app_config.remove_index_server(index_public_key))
-        .map_err(|_| ConfigError::AppConfigError)
+    Ok(ConfigOp::AddFriend(params.name,
+                           params.friend_address.public_key,
+                           params.friend_address.relays,
+                           params.balance))
 }
 
-async fn config_add_friend<'a>(matches: &'a ArgMatches<'a>, 
-                               mut app_config: AppConfig,
-                               node_report: NodeReport) -> Result<(), ConfigError> {
+fn config_add_friend<'a>(matches: &'a ArgMatches<'a>, 
+                         node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
 
     let friend_file = matches.value_of("friend_file").unwrap();
     let friend_name = matches.value_of("friend_name").unwrap();
     let friend_balance_str = matches.value_of("friend_balance").unwrap();
 
     let friend_balance = friend_balance_str.parse::<i128>()
-        .map_err(|_| ConfigError::ParseBalanceError)?;
-
-    for (_friend_public_key, friend_report) in node_report.funder_report.friends {
-        if friend_report.name == friend_name {
-            return Err(ConfigError::FriendNameAlreadyExists);
-        }
-    }
+        .map_err(|_| ConfigError::ParseBalanceError(friend_balance_str.to_owned()))?;
 
     let friend_pathbuf = PathBuf::from(friend_file);
     if !friend_pathbuf.exists() {
-        return Err(ConfigError::FriendFileNotFound);
+        return Err(ConfigError::FriendFileNotFound(friend_pathbuf));
     }
 
     let friend_address = load_friend_from_file(&friend_pathbuf)
-        .map_err(|_| ConfigError::LoadFriendFromFileError)?;
+        .map_err(|e| ConfigError::LoadFriendFromFileError(friend_pathbuf.clone(), AppError::new(e)))?;
 
-    await!(app_config.add_friend(friend_address.public_key,
-                          friend_address.relays,
-                          friend_name.to_owned(),
-                          friend_balance))
-        .map_err(|_| ConfigError::AppConfigError)?;
-    Ok(())
+    plan_add_friend(AddFriendParams {
+        name: friend_name.to_owned(),
+        friend_address,
+        balance: friend_balance,
+    }, node_report)
 }
 
 /// Find a friend's public key given his name
@@ -178,188 +397,786 @@ fn friend_public_key_by_name<'a>(node_report: &'a NodeReport, friend_name: &str)
     None
 }
 
-async fn config_set_friend_relays<'a>(matches: &'a ArgMatches<'a>, 
-                                  mut app_config: AppConfig,
-                                  node_report: NodeReport) -> Result<(), ConfigError> {
+/// Plan updating the relays of a friend
+pub fn plan_set_friend_relays(params: SetFriendRelaysParams,
+                              node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
+
+    let friend_public_key = friend_public_key_by_name(&node_report, &params.name)
+        .ok_or_else(|| ConfigError::FriendNameNotFound(params.name.clone()))?
+        .clone();
+
+    // Just in case, make sure that the the friend we know with this name
+    // has the same public key as inside the provided file.
+    if params.friend_address.public_key != friend_public_key {
+        return Err(ConfigError::FriendPublicKeyMismatch(params.name));
+    }
+
+    Ok(ConfigOp::SetFriendRelays(params.name,
+                                 friend_public_key, 
+                                 params.friend_address.relays))
+}
+
+fn config_set_friend_relays<'a>(matches: &'a ArgMatches<'a>, 
+                                node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
 
     let friend_file = matches.value_of("friend_file").unwrap();
     let friend_name = matches.value_of("friend_name").unwrap();
 
-    let friend_public_key = friend_public_key_by_name(&node_report, friend_name)
-        .ok_or(ConfigError::FriendNameNotFound)?
-        .clone();
+    // Make sure the friend exists before reading the file:
+    friend_public_key_by_name(&node_report, friend_name)
+        .ok_or_else(|| ConfigError::FriendNameNotFound(friend_name.to_owned()))?;
 
     let friend_pathbuf = PathBuf::from(friend_file);
     if !friend_pathbuf.exists() {
-        return Err(ConfigError::FriendFileNotFound);
+        return Err(ConfigError::FriendFileNotFound(friend_pathbuf));
     }
 
     let friend_address = load_friend_from_file(&friend_pathbuf)
-        .map_err(|_| ConfigError::LoadFriendFromFileError)?;
+        .map_err(|e| ConfigError::LoadFriendFromFileError(friend_pathbuf.clone(), AppError::new(e)))?;
 
-    // Just in case, make sure that the the friend we know with this name
-    // has the same public key as inside the provided file.
-    if friend_address.public_key != friend_public_key {
-        return Err(ConfigError::FriendPublicKeyMismatch);
-    }
+    plan_set_friend_relays(SetFriendRelaysParams {
+        name: friend_name.to_owned(),
+        friend_address,
+    }, node_report)
+}
 
-    await!(app_config.set_friend_relays(friend_public_key, 
-                             friend_address.relays))
-        .map_err(|_| ConfigError::AppConfigError)?;
+/// Plan removing a friend
+pub fn plan_remove_friend(params: FriendParams,
+                          node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
 
-    Ok(())
-}
+    let friend_public_key = friend_public_key_by_name(&node_report, &params.name)
+        .ok_or_else(|| ConfigError::FriendNameNotFound(params.name.clone()))?
+        .clone();
 
-async fn config_remove_friend<'a>(matches: &'a ArgMatches<'a>, 
-                                  mut app_config: AppConfig,
-                                  node_report: NodeReport) -> Result<(), ConfigError> {
+    Ok(ConfigOp::RemoveFriend(params.name, friend_public_key))
+}
 
-    let friend_name = matches.value_of("friend_name").unwrap();
+/// Plan enabling a friend
+pub fn plan_enable_friend(params: FriendParams,
+                          node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
 
-    let friend_public_key = friend_public_key_by_name(&node_report, friend_name)
-        .ok_or(ConfigError::FriendNameNotFound)?
+    let friend_public_key = friend_public_key_by_name(&node_report, &params.name)
+        .ok_or_else(|| ConfigError::FriendNameNotFound(params.name.clone()))?
         .clone();
 
-    await!(app_config.remove_friend(friend_public_key))
-        .map_err(|_| ConfigError::AppConfigError)
+    Ok(ConfigOp::EnableFriend(params.name, friend_public_key))
 }
 
-async fn config_enable_friend<'a>(matches: &'a ArgMatches<'a>, 
-                                  mut app_config: AppConfig,
-                                  node_report: NodeReport) -> Result<(), ConfigError> {
-
-    let friend_name = matches.value_of("friend_name").unwrap();
+/// Plan disabling a friend
+pub fn plan_disable_friend(params: FriendParams,
+                           node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
 
-    let friend_public_key = friend_public_key_by_name(&node_report, friend_name)
-        .ok_or(ConfigError::FriendNameNotFound)?
+    let friend_public_key = friend_public_key_by_name(&node_report, &params.name)
+        .ok_or_else(|| ConfigError::FriendNameNotFound(params.name.clone()))?
         .clone();
 
-    await!(app_config.enable_friend(friend_public_key))
-        .map_err(|_| ConfigError::AppConfigError)
+    Ok(ConfigOp::DisableFriend(params.name, friend_public_key))
 }
 
-async fn config_disable_friend<'a>(matches: &'a ArgMatches<'a>, 
-                                   mut app_config: AppConfig,
-                                   node_report: NodeReport) -> Result<(), ConfigError> {
+/// Plan opening requests from a friend
+pub fn plan_open_friend(params: FriendParams,
+                        node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
 
-    let friend_name = matches.value_of("friend_name").unwrap();
-
-    let friend_public_key = friend_public_key_by_name(&node_report, friend_name)
-        .ok_or(ConfigError::FriendNameNotFound)?
+    let friend_public_key = friend_public_key_by_name(&node_report, &params.name)
+        .ok_or_else(|| ConfigError::FriendNameNotFound(params.name.clone()))?
         .clone();
 
-    await!(app_config.disable_friend(friend_public_key))
-        .map_err(|_| ConfigError::AppConfigError)
+    Ok(ConfigOp::OpenFriend(params.name, friend_public_key))
 }
 
-async fn config_open_friend<'a>(matches: &'a ArgMatches<'a>, 
-                                mut app_config: AppConfig,
-                                node_report: NodeReport) -> Result<(), ConfigError> {
-
-    let friend_name = matches.value_of("friend_name").unwrap();
+/// Plan closing requests from a friend
+pub fn plan_close_friend(params: FriendParams,
+                         node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
 
-    let friend_public_key = friend_public_key_by_name(&node_report, friend_name)
-        .ok_or(ConfigError::FriendNameNotFound)?
+    let friend_public_key = friend_public_key_by_name(&node_report, &params.name)
+        .ok_or_else(|| ConfigError::FriendNameNotFound(params.name.clone()))?
         .clone();
 
-    await!(app_config.open_friend(friend_public_key))
-        .map_err(|_| ConfigError::AppConfigError)
+    Ok(ConfigOp::CloseFriend(params.name, friend_public_key))
 }
 
-async fn config_close_friend<'a>(matches: &'a ArgMatches<'a>, 
-                                 mut app_config: AppConfig,
-                                 node_report: NodeReport) -> Result<(), ConfigError> {
+/// Read the friend name argument
+fn friend_params<'a>(matches: &'a ArgMatches<'a>) -> FriendParams {
+    FriendParams {
+        name: matches.value_of("friend_name").unwrap().to_owned(),
+    }
+}
 
-    let friend_name = matches.value_of("friend_name").unwrap();
+/// Plan setting the remote max debt of a friend
+pub fn plan_set_friend_max_debt(params: SetFriendMaxDebtParams,
+                                node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
 
-    let friend_public_key = friend_public_key_by_name(&node_report, friend_name)
-        .ok_or(ConfigError::FriendNameNotFound)?
+    let friend_public_key = friend_public_key_by_name(&node_report, &params.name)
+        .ok_or_else(|| ConfigError::FriendNameNotFound(params.name.clone()))?
         .clone();
 
-    await!(app_config.close_friend(friend_public_key))
-        .map_err(|_| ConfigError::AppConfigError)
+    Ok(ConfigOp::SetFriendRemoteMaxDebt(params.name, friend_public_key, params.max_debt))
 }
 
-async fn config_set_friend_max_debt<'a>(matches: &'a ArgMatches<'a>, 
-                                        mut app_config: AppConfig,
-                                        node_report: NodeReport) -> Result<(), ConfigError> {
+fn config_set_friend_max_debt<'a>(matches: &'a ArgMatches<'a>, 
+                                  node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
 
     let friend_name = matches.value_of("friend_name").unwrap();
     let max_debt_str = matches.value_of("max_debt").unwrap();
 
     let max_debt = max_debt_str.parse::<u128>()
-        .map_err(|_| ConfigError::ParseMaxDebtError)?;
+        .map_err(|_| ConfigError::ParseMaxDebtError(max_debt_str.to_owned()))?;
 
-    let friend_public_key = friend_public_key_by_name(&node_report, friend_name)
-        .ok_or(ConfigError::FriendNameNotFound)?
-        .clone();
-
-    await!(app_config.set_friend_remote_max_debt(friend_public_key, max_debt))
-        .map_err(|_| ConfigError::AppConfigError)
+    plan_set_friend_max_debt(SetFriendMaxDebtParams {
+        name: friend_name.to_owned(),
+        max_debt,
+    }, node_report)
 }
 
-async fn config_reset_friend<'a>(matches: &'a ArgMatches<'a>, 
-                                 mut app_config: AppConfig,
-                                 node_report: NodeReport) -> Result<(), ConfigError> {
-
-    let friend_name = matches.value_of("friend_name").unwrap();
+/// Plan resetting the channel with a friend, accepting the reset terms sent by the friend
+pub fn plan_reset_friend(params: FriendParams,
+                         node_report: &NodeReport) -> Result<ConfigOp, ConfigError> {
 
     let mut opt_friend_pk_report = None;
     for (friend_public_key, friend_report) in &node_report.funder_report.friends {
-        if friend_report.name == friend_name {
+        if friend_report.name == params.name {
             opt_friend_pk_report = Some((friend_public_key, friend_report));
         }
     }
 
     let (friend_public_key, friend_report) = opt_friend_pk_report
-        .ok_or(ConfigError::FriendNameNotFound)?;
+        .ok_or_else(|| ConfigError::FriendNameNotFound(params.name.clone()))?;
 
-    // Obtain the reset token 
-    // (Required as a proof that we already received the remote reset terms):
-    let reset_token = match &friend_report.channel_status {
+    // Obtain the reset terms
+    // (The reset token is required as a proof that we already received the remote reset terms):
+    let remote_reset_terms = match &friend_report.channel_status {
         ChannelStatusReport::Consistent(_) =>
-            return Err(ConfigError::ChannelNotInconsistent),
+            return Err(ConfigError::ChannelNotInconsistent(params.name)),
         ChannelStatusReport::Inconsistent(channel_inconsistent_report) => {
             if let Some(remote_reset_terms) = &channel_inconsistent_report.opt_remote_reset_terms {
-                &remote_reset_terms.reset_token
+                remote_reset_terms
             } else {
-                return Err(ConfigError::UnknownRemoteResetTerms);
+                return Err(ConfigError::UnknownRemoteResetTerms(params.name));
             }
         },
     };
 
-    await!(app_config.reset_friend_channel(friend_public_key.clone(), reset_token.clone()))
-        .map_err(|_| ConfigError::AppConfigError)
+    Ok(ConfigOp::ResetFriendChannel(params.name.clone(), 
+                                    friend_public_key.clone(), 
+                                    remote_reset_terms.clone()))
 }
 
-pub async fn config<'a>(matches: &'a ArgMatches<'a>, mut node_connection: NodeConnection) -> Result<(), ConfigError> {
-    let app_config = node_connection.config()
-        .ok_or(ConfigError::NoPermissions)?
-        .clone();
+/// A single configuration operation on the node.
+/// Every operation corresponds to one `NodeConfig` call.
+#[derive(Debug)]
+pub enum ConfigOp {
+    AddRelay(NamedRelayAddress),
+    RemoveRelay(String, PublicKey),
+    AddIndexServer(NamedIndexServerAddress),
+    RemoveIndexServer(String, PublicKey),
+    AddFriend(String, PublicKey, Vec<RelayAddress>, i128),
+    SetFriendRelays(String, PublicKey, Vec<RelayAddress>),
+    RemoveFriend(String, PublicKey),
+    EnableFriend(String, PublicKey),
+    DisableFriend(String, PublicKey),
+    OpenFriend(String, PublicKey),
+    CloseFriend(String, PublicKey),
+    SetFriendRemoteMaxDebt(String, PublicKey, u128),
+    ResetFriendChannel(String, PublicKey, ResetTermsReport),
+}
 
+impl ConfigOp {
+    /// A user friendly description of the operation
+    pub fn describe(&self) -> String {
+        match self {
+            ConfigOp::AddRelay(named_relay_address) => 
+                format!("add relay {} ({}, {})", 
+                        named_relay_address.name,
+                        public_key_to_string(&named_relay_address.public_key),
+                        named_relay_address.address),
+            ConfigOp::RemoveRelay(name, public_key) => 
+                format!("remove relay {} ({})", name, public_key_to_string(public_key)),
+            ConfigOp::AddIndexServer(named_index_server_address) => 
+                format!("add index server {} ({}, {})", 
+                        named_index_server_address.name,
+                        public_key_to_string(&named_index_server_address.public_key),
+                        named_index_server_address.address),
+            ConfigOp::RemoveIndexServer(name, public_key) => 
+                format!("remove index server {} ({})", name, public_key_to_string(public_key)),
+            ConfigOp::AddFriend(name, public_key, relays, balance) => 
+                format!("add friend {} ({}) with {} relays and balance {}", 
+                        name, public_key_to_string(public_key), relays.len(), balance),
+            ConfigOp::SetFriendRelays(name, _public_key, relays) => 
+                format!("set relays of friend {} ({} relays)", name, relays.len()),
+            ConfigOp::RemoveFriend(name, _public_key) => format!("remove friend {}", name),
+            ConfigOp::EnableFriend(name, _public_key) => format!("enable friend {}", name),
+            ConfigOp::DisableFriend(name, _public_key) => format!("disable friend {}", name),
+            ConfigOp::OpenFriend(name, _public_key) => format!("open friend {}", name),
+            ConfigOp::CloseFriend(name, _public_key) => format!("close friend {}", name),
+            ConfigOp::SetFriendRemoteMaxDebt(name, _public_key, max_debt) => 
+                format!("set remote max debt of friend {} to {}", name, max_debt),
+            ConfigOp::ResetFriendChannel(name, _public_key, remote_reset_terms) => 
+                format!("reset channel with friend {} to balance {}", 
+                        name, remote_reset_terms.balance_for_reset),
+        }
+    }
+}
+
+/// Send a configuration operation to the node
+pub async fn apply_config_op<'a, C: NodeConfig>(app_config: &'a mut C, config_op: ConfigOp) -> Result<(), ConfigError> {
+    let description = config_op.describe();
+    match config_op {
+        ConfigOp::AddRelay(named_relay_address) => 
+            await!(app_config.add_relay(named_relay_address)),
+        ConfigOp::RemoveRelay(_name, public_key) => 
+            await!(app_config.remove_relay(public_key)),
+        ConfigOp::AddIndexServer(named_index_server_address) => 
+            await!(app_config.add_index_server(named_index_server_address)),
+        ConfigOp::RemoveIndexServer(_name, public_key) => 
+            await!(app_config.remove_index_server(public_key)),
+        ConfigOp::AddFriend(name, public_key, relays, balance) => 
+            await!(app_config.add_friend(public_key, relays, name, balance)),
+        ConfigOp::SetFriendRelays(_name, public_key, relays) => 
+            await!(app_config.set_friend_relays(public_key, relays)),
+        ConfigOp::RemoveFriend(_name, public_key) => 
+            await!(app_config.remove_friend(public_key)),
+        ConfigOp::EnableFriend(_name, public_key) => 
+            await!(app_config.enable_friend(public_key)),
+        ConfigOp::DisableFriend(_name, public_key) => 
+            await!(app_config.disable_friend(public_key)),
+        ConfigOp::OpenFriend(_name, public_key) => 
+            await!(app_config.open_friend(public_key)),
+        ConfigOp::CloseFriend(_name, public_key) => 
+            await!(app_config.close_friend(public_key)),
+        ConfigOp::SetFriendRemoteMaxDebt(_name, public_key, max_debt) => 
+            await!(app_config.set_friend_remote_max_debt(public_key, max_debt)),
+        ConfigOp::ResetFriendChannel(_name, public_key, remote_reset_terms) => 
+            await!(app_config.reset_friend_channel(public_key, remote_reset_terms)),
+    }.map_err(|e| node_error(e, |e| ConfigError::AppConfigError(description, e)))
+}
+
+/// A relay or an index server entry in a node configuration file
+#[derive(Debug, Serialize, Deserialize)]
+struct ServerEntry {
+    name: String,
+    /// Path to a relay/index server ticket file.
+    /// Relative paths are relative to the directory of the node configuration file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ticket: Option<String>,
+    /// Contents of a relay/index server ticket file, embedded in the node configuration file.
+    /// Used when `ticket` is not specified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ticket_data: Option<String>,
+}
+
+/// A friend entry in a node configuration file
+#[derive(Debug, Serialize, Deserialize)]
+struct FriendEntry {
+    name: String,
+    /// Path to a friend ticket file.
+    /// Relative paths are relative to the directory of the node configuration file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ticket: Option<String>,
+    /// Contents of a friend ticket file, embedded in the node configuration file.
+    /// Used when `ticket` is not specified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ticket_data: Option<String>,
+    /// Initial balance. Only used when the friend is added.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    balance: Option<String>,
+    /// Wanted remote max debt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_debt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    open: Option<bool>,
+}
+
+/// Desired configuration of a node, as described in a node configuration file.
+///
+/// The file may be in TOML format (Any extension other than `.json`) or in JSON format. 
+/// Example (TOML):
+/// ```text
+/// [[relays]]
+/// name = "relay0"
+/// ticket = "relay0.ticket"
+///
+/// [[index_servers]]
+/// name = "index0"
+/// ticket = "index0.ticket"
+///
+/// [[friends]]
+/// name = "bob"
+/// ticket = "bob.ticket"
+/// balance = "0"
+/// max_debt = "100"
+/// enabled = true
+/// open = true
+/// ```
+///
+/// Instead of `ticket`, every entry may contain `ticket_data`: the contents of the ticket file.
+/// This is the format produced by `config export`.
+///
+/// Sections that are missing from the file are not managed: the node's current relays, 
+/// index servers or friends are left as they are.
+/// Relays and index servers that are not listed in a present section are removed from the node.
+/// Friends are only removed when pruning was requested, as removing a friend
+/// destroys the mutual credit channel.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NodeConfigFile {
+    relays: Option<Vec<ServerEntry>>,
+    index_servers: Option<Vec<ServerEntry>>,
+    friends: Option<Vec<FriendEntry>>,
+}
+
+/// Parameters for applying a node configuration file
+#[derive(Debug, Clone)]
+pub struct ApplyParams {
+    pub node_config_pathbuf: PathBuf,
+    /// Remove friends that are not listed in the file
+    pub prune_friends: bool,
+}
+
+/// Obtain the path of the ticket file of an entry in a node configuration file.
+/// Embedded tickets are first written into a file inside `temp_dir`.
+fn ticket_pathbuf(base_dir: &Path, 
+                  temp_dir: &Path,
+                  file_name: &str,
+                  entry_name: &str,
+                  opt_ticket: &Option<String>, 
+                  opt_ticket_data: &Option<String>) -> Result<PathBuf, ConfigError> {
+
+    match (opt_ticket, opt_ticket_data) {
+        (Some(ticket), _) => {
+            let pathbuf = PathBuf::from(ticket);
+            Ok(if pathbuf.is_relative() {
+                base_dir.join(pathbuf)
+            } else {
+                pathbuf
+            })
+        },
+        (None, Some(ticket_data)) => {
+            let pathbuf = temp_dir.join(file_name);
+            fs::write(&pathbuf, ticket_data)
+                .map_err(ConfigError::WriteTempTicketError)?;
+            Ok(pathbuf)
+        },
+        (None, None) => Err(ConfigError::MissingTicket(entry_name.to_owned())),
+    }
+}
+
+/// Make sure that names and public keys are not used twice
+fn check_unique<'a, I>(entries: I) -> Result<(), ConfigError> 
+where
+    I: Iterator<Item=(&'a str, &'a PublicKey)>,
+{
+    let mut names: Vec<&str> = Vec::new();
+    let mut public_keys: Vec<&PublicKey> = Vec::new();
+    for (name, public_key) in entries {
+        if names.contains(&name) {
+            return Err(ConfigError::DuplicateName(name.to_owned()));
+        }
+        if public_keys.contains(&public_key) {
+            return Err(ConfigError::DuplicatePublicKey(public_key_to_string(public_key)));
+        }
+        names.push(name);
+        public_keys.push(public_key);
+    }
+    Ok(())
+}
+
+/// Load a node configuration file, and calculate the operations required to 
+/// bring the node from its current configuration (`node_report`) to the
+/// configuration described in the file.
+pub fn plan_node_config(params: &ApplyParams, 
+                        node_report: &NodeReport) -> Result<Vec<ConfigOp>, ConfigError> {
+
+    let node_config_pathbuf = &params.node_config_pathbuf;
+
+    if !node_config_pathbuf.exists() {
+        return Err(ConfigError::NodeConfigFileNotFound(node_config_pathbuf.to_path_buf()));
+    }
+
+    let data = fs::read_to_string(node_config_pathbuf)
+        .map_err(ConfigError::ReadNodeConfigFileError)?;
+
+    let is_json = node_config_pathbuf.extension().map(|ext| ext == "json").unwrap_or(false);
+    let node_config_file: NodeConfigFile = if is_json {
+        serde_json::from_str(&data)
+            .map_err(|e| ConfigError::ParseNodeConfigFileError(Box::new(e)))?
+    } else {
+        toml::from_str(&data)
+            .map_err(|e| ConfigError::ParseNodeConfigFileError(Box::new(e)))?
+    };
+
+    let base_dir = node_config_pathbuf.parent()
+        .map(|parent| parent.to_path_buf())
+        .unwrap_or_else(PathBuf::new);
+
+    // Used for loading embedded tickets:
+    let temp_dir = TempDir::new("stctrl")
+        .map_err(ConfigError::WriteTempTicketError)?;
+
+    // Load all tickets:
+    let mut wanted_relays = Vec::new();
+    for (i, server_entry) in node_config_file.relays.iter().flatten().enumerate() {
+        let relay_pathbuf = ticket_pathbuf(&base_dir, temp_dir.path(), &format!("relay{}", i),
+                                           &server_entry.name,
+                                           &server_entry.ticket, &server_entry.ticket_data)?;
+        if !relay_pathbuf.exists() {
+            return Err(ConfigError::RelayFileNotFound(relay_pathbuf));
+        }
+        let relay_address = load_relay_from_file(&relay_pathbuf)
+            .map_err(|e| ConfigError::LoadRelayFromFileError(relay_pathbuf.clone(), AppError::new(e)))?;
+        wanted_relays.push(NamedRelayAddress {
+            public_key: relay_address.public_key,
+            address: relay_address.address,
+            name: server_entry.name.clone(),
+        });
+    }
+
+    let mut wanted_index_servers = Vec::new();
+    for (i, server_entry) in node_config_file.index_servers.iter().flatten().enumerate() {
+        let index_pathbuf = ticket_pathbuf(&base_dir, temp_dir.path(), &format!("index{}", i),
+                                           &server_entry.name,
+                                           &server_entry.ticket, &server_entry.ticket_data)?;
+        if !index_pathbuf.exists() {
+            return Err(ConfigError::IndexFileNotFound(index_pathbuf));
+        }
+        let index_server_address = load_index_server_from_file(&index_pathbuf)
+            .map_err(|e| ConfigError::LoadIndexFromFileError(index_pathbuf.clone(), AppError::new(e)))?;
+        wanted_index_servers.push(NamedIndexServerAddress {
+            public_key: index_server_address.public_key,
+            address: index_server_address.address,
+            name: server_entry.name.clone(),
+        });
+    }
+
+    let mut wanted_friends = Vec::new();
+    for (i, friend_entry) in node_config_file.friends.iter().flatten().enumerate() {
+        let friend_pathbuf = ticket_pathbuf(&base_dir, temp_dir.path(), &format!("friend{}", i),
+                                            &friend_entry.name,
+                                            &friend_entry.ticket, &friend_entry.ticket_data)?;
+        if !friend_pathbuf.exists() {
+            return Err(ConfigError::FriendFileNotFound(friend_pathbuf));
+        }
+        let friend_address = load_friend_from_file(&friend_pathbuf)
+            .map_err(|e| ConfigError::LoadFriendFromFileError(friend_pathbuf.clone(), AppError::new(e)))?;
+        let balance = match &friend_entry.balance {
+            Some(balance_str) => balance_str.parse::<i128>()
+                .map_err(|_| ConfigError::ParseBalanceError(balance_str.clone()))?,
+            None => 0,
+        };
+        let opt_max_debt = match &friend_entry.max_debt {
+            Some(max_debt_str) => Some(max_debt_str.parse::<u128>()
+                .map_err(|_| ConfigError::ParseMaxDebtError(max_debt_str.clone()))?),
+            None => None,
+        };
+        wanted_friends.push((friend_entry, friend_address, balance, opt_max_debt));
+    }
+
+    check_unique(wanted_relays.iter()
+                 .map(|relay| (relay.name.as_str(), &relay.public_key)))?;
+    check_unique(wanted_index_servers.iter()
+                 .map(|index_server| (index_server.name.as_str(), &index_server.public_key)))?;
+    check_unique(wanted_friends.iter()
+                 .map(|(friend_entry, friend_address, _, _)| 
+                      (friend_entry.name.as_str(), &friend_address.public_key)))?;
+
+    let mut config_ops = Vec::new();
+
+    // Relays:
+    for named_relay_address in &node_report.funder_report.relays {
+        let is_wanted = wanted_relays.iter().any(|wanted_relay| wanted_relay == named_relay_address);
+        if node_config_file.relays.is_some() && !is_wanted {
+            config_ops.push(ConfigOp::RemoveRelay(named_relay_address.name.clone(),
+                                                  named_relay_address.public_key.clone()));
+        }
+    }
+    for wanted_relay in wanted_relays {
+        if !node_report.funder_report.relays.contains(&wanted_relay) {
+            config_ops.push(ConfigOp::AddRelay(wanted_relay));
+        }
+    }
+
+    // Index servers:
+    let index_servers = &node_report.index_client_report.index_servers;
+    for named_index_server_address in index_servers {
+        let is_wanted = wanted_index_servers.iter()
+            .any(|wanted_index_server| wanted_index_server == named_index_server_address);
+        if node_config_file.index_servers.is_some() && !is_wanted {
+            config_ops.push(ConfigOp::RemoveIndexServer(named_index_server_address.name.clone(),
+                                                        named_index_server_address.public_key.clone()));
+        }
+    }
+    for wanted_index_server in wanted_index_servers {
+        if !index_servers.contains(&wanted_index_server) {
+            config_ops.push(ConfigOp::AddIndexServer(wanted_index_server));
+        }
+    }
+
+    // Friends:
+    let prune_friends = params.prune_friends && node_config_file.friends.is_some();
+    for (friend_public_key, friend_report) in &node_report.funder_report.friends {
+        let is_wanted = wanted_friends.iter()
+            .any(|(_, friend_address, _, _)| &friend_address.public_key == friend_public_key);
+        if prune_friends && !is_wanted {
+            config_ops.push(ConfigOp::RemoveFriend(friend_report.name.clone(), 
+                                                   friend_public_key.clone()));
+        }
+    }
+
+    for (friend_entry, friend_address, balance, opt_max_debt) in wanted_friends {
+        let name = friend_entry.name.clone();
+        let public_key = friend_address.public_key.clone();
+
+        let friend_report = match node_report.funder_report.friends.get(&public_key) {
+            Some(friend_report) => {
+                // Friends can not be renamed without removing them, 
+                // which would destroy the mutual credit channel:
+                if friend_report.name != name {
+                    return Err(ConfigError::FriendNameMismatch(name));
+                }
+                friend_report
+            },
+            None => {
+                // Name based commands can not tell apart two friends with the same name.
+                // Without pruning, an existing friend by this name is kept:
+                let name_taken = node_report.funder_report.friends.values()
+                    .any(|friend_report| friend_report.name == name);
+                if name_taken && !prune_friends {
+                    return Err(ConfigError::FriendNameAlreadyExists(name));
+                }
+
+                // A new friend. The friend's settings are applied right after it is added:
+                config_ops.push(ConfigOp::AddFriend(name.clone(), public_key.clone(), 
+                                                    friend_address.relays, balance));
+                if let Some(max_debt) = opt_max_debt {
+                    config_ops.push(ConfigOp::SetFriendRemoteMaxDebt(name.clone(), public_key.clone(), max_debt));
+                }
+                match friend_entry.open {
+                    Some(true) => config_ops.push(ConfigOp::OpenFriend(name.clone(), public_key.clone())),
+                    Some(false) => config_ops.push(ConfigOp::CloseFriend(name.clone(), public_key.clone())),
+                    None => {},
+                }
+                match friend_entry.enabled {
+                    Some(true) => config_ops.push(ConfigOp::EnableFriend(name, public_key)),
+                    Some(false) => config_ops.push(ConfigOp::DisableFriend(name, public_key)),
+                    None => {},
+                }
+                continue;
+            },
+        };
+
+        if friend_report.remote_relays != friend_address.relays {
+            config_ops.push(ConfigOp::SetFriendRelays(name.clone(), public_key.clone(), 
+                                                      friend_address.relays));
+        }
+
+        if let Some(max_debt) = opt_max_debt {
+            if friend_report.wanted_remote_max_debt != max_debt {
+                config_ops.push(ConfigOp::SetFriendRemoteMaxDebt(name.clone(), public_key.clone(), max_debt));
+            }
+        }
+
+        let is_open = friend_report.wanted_local_requests_status == RequestsStatusReport::Open;
+        match friend_entry.open {
+            Some(true) if !is_open => 
+                config_ops.push(ConfigOp::OpenFriend(name.clone(), public_key.clone())),
+            Some(false) if is_open => 
+                config_ops.push(ConfigOp::CloseFriend(name.clone(), public_key.clone())),
+            _ => {},
+        }
+
+        let is_enabled = friend_report.status == FriendStatusReport::Enabled;
+        match friend_entry.enabled {
+            Some(true) if !is_enabled => 
+                config_ops.push(ConfigOp::EnableFriend(name, public_key)),
+            Some(false) if is_enabled => 
+                config_ops.push(ConfigOp::DisableFriend(name, public_key)),
+            _ => {},
+        }
+    }
+
+    Ok(config_ops)
+}
+
+/// Store a ticket into a temporary file, and return the contents of the file
+fn embed_ticket<F, E>(temp_dir: &Path, 
+                      file_name: &str, 
+                      store: F) -> Result<String, ConfigError>
+where
+    F: FnOnce(&PathBuf) -> Result<(), E>,
+    E: fmt::Debug,
+{
+    let pathbuf = temp_dir.join(file_name);
+    store(&pathbuf)
+        .map_err(|e| ConfigError::StoreTicketError(AppError::new(e)))?;
+    fs::read_to_string(&pathbuf)
+        .map_err(ConfigError::WriteTempTicketError)
+}
+
+/// Write a snapshot of the node's configuration (relays, index servers and friends)
+/// into a file. All tickets are embedded inside the file, 
+/// so that it can be later used with `config apply`.
+fn config_export<'a>(matches: &'a ArgMatches<'a>, 
+                     node_report: &NodeReport) -> Result<(), ConfigError> {
+
+    let output_file = matches.value_of("output_file").unwrap();
+    let output_pathbuf = PathBuf::from(output_file);
+
+    if output_pathbuf.exists() {
+        return Err(ConfigError::OutputFileAlreadyExists(output_pathbuf));
+    }
+
+    if matches.is_present("dry_run") {
+        println!("Dry run. Would write the node configuration to {}", output_pathbuf.display());
+        return Ok(());
+    }
+
+    let temp_dir = TempDir::new("stctrl")
+        .map_err(ConfigError::WriteTempTicketError)?;
+
+    let mut relays = Vec::new();
+    for (i, named_relay_address) in node_report.funder_report.relays.iter().enumerate() {
+        let relay_address: RelayAddress = named_relay_address.clone().into();
+        let ticket_data = embed_ticket(temp_dir.path(), &format!("relay{}", i),
+            |pathbuf| store_relay_to_file(&relay_address, pathbuf))?;
+        relays.push(ServerEntry {
+            name: named_relay_address.name.clone(),
+            ticket: None,
+            ticket_data: Some(ticket_data),
+        });
+    }
+
+    let mut index_servers = Vec::new();
+    for (i, named_index_server_address) in node_report.index_client_report.index_servers.iter().enumerate() {
+        let index_server_address = IndexServerAddress {
+            public_key: named_index_server_address.public_key.clone(),
+            address: named_index_server_address.address.clone(),
+        };
+        let ticket_data = embed_ticket(temp_dir.path(), &format!("index{}", i),
+            |pathbuf| store_index_server_to_file(&index_server_address, pathbuf))?;
+        index_servers.push(ServerEntry {
+            name: named_index_server_address.name.clone(),
+            ticket: None,
+            ticket_data: Some(ticket_data),
+        });
+    }
+
+    let mut friends = Vec::new();
+    for (i, (friend_public_key, friend_report)) in node_report.funder_report.friends.iter().enumerate() {
+        let friend_address = FriendAddress {
+            public_key: friend_public_key.clone(),
+            relays: friend_report.remote_relays.clone(),
+        };
+        let ticket_data = embed_ticket(temp_dir.path(), &format!("friend{}", i),
+            |pathbuf| store_friend_to_file(&friend_address, pathbuf))?;
+
+        let balance = match &friend_report.channel_status {
+            ChannelStatusReport::Consistent(tc_report) => tc_report.balance.balance,
+            ChannelStatusReport::Inconsistent(channel_inconsistent_report) => 
+                channel_inconsistent_report.local_reset_terms_balance,
+        };
+
+        friends.push(FriendEntry {
+            name: friend_report.name.clone(),
+            ticket: None,
+            ticket_data: Some(ticket_data),
+            balance: Some(balance.to_string()),
+            max_debt: Some(friend_report.wanted_remote_max_debt.to_string()),
+            enabled: Some(friend_report.status == FriendStatusReport::Enabled),
+            open: Some(friend_report.wanted_local_requests_status == RequestsStatusReport::Open),
+        });
+    }
+
+    let node_config_file = NodeConfigFile {
+        relays: Some(relays),
+        index_servers: Some(index_servers),
+        friends: Some(friends),
+    };
+
+    let is_json = output_pathbuf.extension().map(|ext| ext == "json").unwrap_or(false);
+    let data = if is_json {
+        serde_json::to_string_pretty(&node_config_file)
+            .map_err(|e| ConfigError::StoreNodeConfigFileError(Box::new(e)))?
+    } else {
+        toml::to_string(&node_config_file)
+            .map_err(|e| ConfigError::StoreNodeConfigFileError(Box::new(e)))?
+    };
+
+    fs::write(&output_pathbuf, data)
+        .map_err(|e| ConfigError::StoreNodeConfigFileError(Box::new(e)))
+}
+
+/// Bring the node to the configuration described in a node configuration file,
+/// using a minimal amount of configuration operations.
+fn config_apply<'a>(matches: &'a ArgMatches<'a>, 
+                    node_report: &NodeReport) -> Result<Vec<ConfigOp>, ConfigError> {
+
+    let node_config_file = matches.value_of("node_config_file").unwrap();
+    let params = ApplyParams {
+        node_config_pathbuf: PathBuf::from(node_config_file),
+        prune_friends: matches.is_present("prune"),
+    };
+
+    plan_node_config(&params, node_report)
+}
+
+pub async fn config<'a, N: Node>(matches: &'a ArgMatches<'a>, node_connection: &'a mut N) -> Result<(), ConfigError> {
     // Obtain current report:
     let app_report = node_connection.report();
     let (node_report, incoming_mutations) = await!(app_report.incoming_reports())
-        .map_err(|_| ConfigError::GetReportError)?;
+        .map_err(|e| node_error(e, ConfigError::GetReportError))?;
     // We currently don't need live updates about report mutations:
     drop(incoming_mutations);
     drop(app_report);
 
-    match matches.subcommand() {
-        ("add-relay", Some(matches)) => await!(config_add_relay(matches, app_config, node_report))?,
-        ("remove-relay", Some(matches)) => await!(config_remove_relay(matches, app_config, node_report))?,
-        ("add-index", Some(matches)) => await!(config_add_index(matches, app_config, node_report))?,
-        ("remove-index", Some(matches)) => await!(config_remove_index(matches, app_config, node_report))?,
-        ("add-friend", Some(matches)) => await!(config_add_friend(matches, app_config, node_report))?,
-        ("set-friend-relays", Some(matches)) => await!(config_set_friend_relays(matches, app_config, node_report))?,
-        ("remove-friend", Some(matches)) => await!(config_remove_friend(matches, app_config, node_report))?,
-        ("enable-friend", Some(matches)) => await!(config_enable_friend(matches, app_config, node_report))?,
-        ("disable-friend", Some(matches)) => await!(config_disable_friend(matches, app_config, node_report))?,
-        ("open-friend", Some(matches)) => await!(config_open_friend(matches, app_config, node_report))?,
-        ("close-friend", Some(matches)) => await!(config_close_friend(matches, app_config, node_report))?,
-        ("set-friend-max-debt", Some(matches)) => await!(config_set_friend_max_debt(matches, app_config, node_report))?,
-        ("reset-friend", Some(matches)) => await!(config_reset_friend(matches, app_config, node_report))?,
+    // Exporting the configuration only requires reading the report:
+    if let ("export", Some(matches)) = matches.subcommand() {
+        return config_export(matches, &node_report);
+    }
+
+    let mut app_config = node_connection.config()
+        .ok_or(ConfigError::NoPermissions)?
+        .clone();
+
+    let dry_run = matches.is_present("dry_run");
+
+    let config_ops = match matches.subcommand() {
+        ("add-relay", Some(matches)) => vec![config_add_relay(matches, &node_report)?],
+        ("remove-relay", Some(matches)) => vec![config_remove_relay(matches, &node_report)?],
+        ("add-index", Some(matches)) => vec![config_add_index(matches, &node_report)?],
+        ("remove-index", Some(matches)) => vec![config_remove_index(matches, &node_report)?],
+        ("add-friend", Some(matches)) => vec![config_add_friend(matches, &node_report)?],
+        ("set-friend-relays", Some(matches)) => vec![config_set_friend_relays(matches, &node_report)?],
+        ("remove-friend", Some(matches)) => vec![plan_remove_friend(friend_params(matches), &node_report)?],
+        ("enable-friend", Some(matches)) => vec![plan_enable_friend(friend_params(matches), &node_report)?],
+        ("disable-friend", Some(matches)) => vec![plan_disable_friend(friend_params(matches), &node_report)?],
+        ("open-friend", Some(matches)) => vec![plan_open_friend(friend_params(matches), &node_report)?],
+        ("close-friend", Some(matches)) => vec![plan_close_friend(friend_params(matches), &node_report)?],
+        ("set-friend-max-debt", Some(matches)) => vec![config_set_friend_max_debt(matches, &node_report)?],
+        ("reset-friend", Some(matches)) => vec![plan_reset_friend(friend_params(matches), &node_report)?],
+        ("apply", Some(matches)) => config_apply(matches, &node_report)?,
         _ => unreachable!(),
+    };
+
+    if config_ops.is_empty() {
+        println!("Node configuration is up to date.");
+        return Ok(());
+    }
+
+    // Operations are always listed when applying a configuration file:
+    let verbose = dry_run || matches.subcommand_name() == Some("apply");
+
+    if dry_run {
+        println!("Dry run. The following operations would be performed:");
+    }
+
+    for config_op in config_ops {
+        if verbose {
+            println!("{}", config_op.describe());
+        }
+        if !dry_run {
+            await!(apply_config_op(&mut app_config, config_op))?;
+        }
     }
 
     Ok(())
@@ -0,0 +1,28 @@
+use std::env;
+use std::path::PathBuf;
+
+use clap::ArgMatches;
+
+use crate::profile::Profile;
+
+pub const STCTRL_DATA_DIR: &str = "STCTRL_DATA_DIR";
+
+/// Default data directory, relative to the user's home directory
+const DEFAULT_DATA_DIR: &str = ".stctrl";
+
+/// Get the directory where stctrl keeps its local data (receipts, etc.)
+///
+/// Taken from the (global) `--data-dir` argument, then from the `STCTRL_DATA_DIR` 
+/// environment variable and the profile (See `Profile::setting_or_env`),
+/// and otherwise defaults to `~/.stctrl`.
+pub fn data_dir<'a>(matches: &'a ArgMatches<'a>, profile: &'a Profile) -> Option<PathBuf> {
+    if let Some(data_dir) = matches.value_of("data_dir") {
+        return Some(PathBuf::from(data_dir));
+    }
+    let opt_env_data_dir = env::var_os(STCTRL_DATA_DIR).map(PathBuf::from);
+    if let Some(data_dir) = profile.setting_or_env(profile.data_dir.clone(), opt_env_data_dir) {
+        return Some(data_dir);
+    }
+    env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(DEFAULT_DATA_DIR))
+}
@@ -0,0 +1,12 @@
+//! Encoding of binary data (ids, hashes, signatures, tokens, etc.) in stctrl's
+//! outputs and files. Uses URL safe base64 without padding.
+
+/// Encode binary data into a string
+pub fn bytes_to_string(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+/// Decode binary data encoded with `bytes_to_string`
+pub fn string_to_bytes(data_str: &str) -> Result<Vec<u8>, base64::DecodeError> {
+    base64::decode_config(data_str, base64::URL_SAFE_NO_PAD)
+}
@@ -1,46 +1,288 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::cmp::Reverse;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
 use clap::ArgMatches;
+use prettytable::Table;
+use rand::{Rng, RngCore};
+use chrono::{DateTime, NaiveDate, Utc};
 
-use app::{NodeConnection, AppRoutes, AppSendFunds,
-            string_to_public_key, PublicKey};
+use app::{string_to_public_key, public_key_to_string, 
+            load_friend_from_file, PublicKey};
 
 use app::uid::gen_uid;
 use app::invoice::{InvoiceId, INVOICE_ID_LEN};
 use app::route::{FriendsRoute, RouteWithCapacity};
+use app::report::NodeReport;
+
+use crate::invoice::{Invoice, InvoiceFileError, load_invoice_from_file, store_invoice_to_file, 
+    invoice_id_to_string, string_to_invoice_id};
+use crate::receipt::{ReceiptFile, ReceiptFileError, store_receipt_to_file, 
+    public_keys_to_strings};
+use crate::encoding::bytes_to_string;
+use crate::data_dir::{data_dir, STCTRL_DATA_DIR};
+use crate::ledger::{LedgerEntry, LedgerError, append_to_ledger, load_ledger,
+    LEDGER_STATUS_SUCCESS, LEDGER_STATUS_FAILURE};
+use crate::address_book::{Contact, AddressBookError, contact_by_name, 
+    load_address_book, store_address_book};
+use crate::batch::{BatchPayment, BatchResult, BatchFileError, load_batch_from_file, 
+    store_batch_results_to_file};
+use crate::output::{OutputFormat, OutputError, print_output};
+use crate::profile::Profile;
+use crate::node::{Node, NodeError, NodeRoutes, NodeSendFunds};
+
+/// Receipts are stored in this directory, inside the data directory
+const RECEIPTS_DIR: &str = "receipts";
+
+/// The ledger of all payments, inside the data directory
+const LEDGER_FILE: &str = "ledger.jsonl";
+
+/// The address book of named destinations, inside the data directory
+const ADDRESS_BOOK_FILE: &str = "address_book.toml";
 
 #[derive(Debug)]
 pub enum FundsError {
-    GetReportError,
+    GetReportError(NodeError),
+    /// The node did not respond in time
+    Timeout(Duration),
     NoFundsPermissions,
     NoRoutesPermissions,
-    InvalidDestination,
-    ParseAmountError,
-    AppRoutesError,
-    SendFundsError,
+    InvalidDestination(String),
+    ParseAmountError(String),
+    AppRoutesError(NodeError),
+    SendFundsError(NodeError),
     NoSuitableRoute,
-    ReceiptAckError,
+    ReceiptAckError(NodeError),
+    InvoiceFileNotFound(PathBuf),
+    LoadInvoiceError(InvoiceFileError),
+    OutputFileAlreadyExists(PathBuf),
+    StoreInvoiceError(InvoiceFileError),
+    NoDataDir,
+    FriendNameNotFound(String),
+    ParseMaxFeeError(String),
+    MaxFeeExceeded,
+    FeesOverflow,
+    ReadConfirmationError(io::Error),
+    PaymentNotConfirmed,
+    OutputError(OutputError),
+    ParseAttemptsError(String),
+    ParseDeadlineError(String),
+    BatchFileNotFound(PathBuf),
+    LoadBatchError(BatchFileError),
+    /// Invalid row in a batch file. Rows are numbered from 1.
+    InvalidBatchPayment(usize),
+    StoreBatchResultsError(BatchFileError),
+    /// Some payments of a batch failed (Amount of failed payments)
+    BatchPaymentsFailed(usize),
+    StoreLedgerError(LedgerError),
+    LoadLedgerError(LedgerError),
+    ParseDateError(String),
+    LoadAddressBookError(AddressBookError),
+    StoreAddressBookError(AddressBookError),
+    ContactNameNotFound(String),
+    DuplicateContactName(String),
+    /// All attempts of a payment failed
+    AttemptsFailed(Vec<PaymentAttempt>),
+    /// A part of a split payment failed (Part number, amount of parts). 
+    /// The previous parts were already paid.
+    PaymentPartFailed(usize, usize, Box<FundsError>),
+    /// A split payment has a receipt for every part, which can not be stored into a single file
+    SplitReceiptOut,
 }
 
+impl fmt::Display for FundsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FundsError::GetReportError(e) => write!(f, "failed to obtain a report from the node: {}", e),
+            FundsError::Timeout(timeout) => 
+                write!(f, "the node did not respond within {} seconds", timeout.as_secs()),
+            FundsError::NoFundsPermissions => 
+                write!(f, "the application has no permissions to send funds"),
+            FundsError::NoRoutesPermissions => 
+                write!(f, "the application has no permissions to request routes"),
+            FundsError::InvalidDestination(destination_str) => 
+                write!(f, "{:?} is not a public key, a friend, a contact or a ticket file", 
+                       destination_str),
+            FundsError::ParseAmountError(amount_str) => write!(f, "invalid amount: {:?}", amount_str),
+            FundsError::AppRoutesError(e) => write!(f, "failed to request routes from the node: {}", e),
+            FundsError::SendFundsError(e) => write!(f, "the payment failed: {}", e),
+            FundsError::NoSuitableRoute => 
+                write!(f, "no route with enough capacity was found to the destination"),
+            FundsError::ReceiptAckError(e) => write!(f, "failed to acknowledge a receipt: {}", e),
+            FundsError::InvoiceFileNotFound(path) => 
+                write!(f, "invoice file not found: {}", path.display()),
+            FundsError::LoadInvoiceError(e) => write!(f, "{}", e),
+            FundsError::OutputFileAlreadyExists(path) => 
+                write!(f, "output file already exists: {}", path.display()),
+            FundsError::StoreInvoiceError(e) => write!(f, "{}", e),
+            FundsError::NoDataDir => 
+                write!(f, "no data directory. Use --data-dir or set {}", STCTRL_DATA_DIR),
+            FundsError::FriendNameNotFound(name) => write!(f, "no friend named {:?}", name),
+            FundsError::ParseMaxFeeError(max_fee_str) => write!(f, "invalid max fee: {:?}", max_fee_str),
+            FundsError::MaxFeeExceeded => write!(f, "the fees of the payment exceed the max fee"),
+            FundsError::FeesOverflow => write!(f, "overflow when calculating fees"),
+            FundsError::ReadConfirmationError(e) => write!(f, "failed to read confirmation: {}", e),
+            FundsError::PaymentNotConfirmed => write!(f, "the payment was not confirmed"),
+            FundsError::OutputError(e) => write!(f, "{}", e),
+            FundsError::ParseAttemptsError(attempts_str) => 
+                write!(f, "invalid amount of attempts: {:?}", attempts_str),
+            FundsError::ParseDeadlineError(deadline_str) => 
+                write!(f, "invalid deadline: {:?}", deadline_str),
+            FundsError::BatchFileNotFound(path) => 
+                write!(f, "batch file not found: {}", path.display()),
+            FundsError::LoadBatchError(e) => write!(f, "invalid batch file: {}", e),
+            FundsError::InvalidBatchPayment(row) => 
+                write!(f, "invalid payment at row {} of the batch file", row),
+            FundsError::StoreBatchResultsError(e) => write!(f, "failed to store batch results: {}", e),
+            FundsError::BatchPaymentsFailed(num_failed) => 
+                write!(f, "{} payments of the batch failed", num_failed),
+            FundsError::StoreLedgerError(e) | FundsError::LoadLedgerError(e) => write!(f, "{}", e),
+            FundsError::ParseDateError(date_str) => 
+                write!(f, "invalid date: {:?}. Use YYYY-MM-DD or RFC 3339", date_str),
+            FundsError::LoadAddressBookError(e) 
+                | FundsError::StoreAddressBookError(e) => write!(f, "{}", e),
+            FundsError::ContactNameNotFound(name) => write!(f, "no contact named {:?}", name),
+            FundsError::DuplicateContactName(name) => 
+                write!(f, "a contact named {:?} already exists", name),
+            FundsError::AttemptsFailed(attempts) => 
+                write!(f, "the payment failed after {} attempts", attempts.len()),
+            FundsError::PaymentPartFailed(part, num_parts, e) => 
+                write!(f, "part {}/{} of the payment failed ({} parts were already paid): {}", 
+                       part, num_parts, part - 1, e),
+            FundsError::SplitReceiptOut => 
+                write!(f, "a receipt output file can not be used with a split payment"),
+        }
+    }
+}
 
-/// Choose a route for pushing `amount` credits
-fn choose_route(routes_with_capacity: Vec<RouteWithCapacity>, amount: u128) 
-    -> Result<FriendsRoute, FundsError> {
+impl Error for FundsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FundsError::GetReportError(e)
+                | FundsError::AppRoutesError(e)
+                | FundsError::SendFundsError(e)
+                | FundsError::ReceiptAckError(e) => Some(e),
+            FundsError::LoadInvoiceError(e) | FundsError::StoreInvoiceError(e) => Some(e),
+            FundsError::ReadConfirmationError(e) => Some(e),
+            FundsError::OutputError(e) => Some(e),
+            FundsError::LoadBatchError(e) | FundsError::StoreBatchResultsError(e) => Some(e),
+            FundsError::StoreLedgerError(e) | FundsError::LoadLedgerError(e) => Some(e),
+            FundsError::LoadAddressBookError(e) | FundsError::StoreAddressBookError(e) => Some(e),
+            FundsError::PaymentPartFailed(_, _, e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
 
-    // We naively select the first route we find suitable:
-    // TODO: Possibly improve this later:
-    for route_with_capacity in routes_with_capacity {
+/// Convert a failed node operation using `to_error`. A timeout is reported on its own.
+fn node_error<F: FnOnce(NodeError) -> FundsError>(e: NodeError, to_error: F) -> FundsError {
+    match e {
+        NodeError::Timeout(timeout) => FundsError::Timeout(timeout),
+        e => to_error(e),
+    }
+}
 
-        // TODO: Is this dangerous? How can we do this safely?
-        let length = route_with_capacity.route.len() as u128;
+impl From<OutputError> for FundsError {
+    fn from(e: OutputError) -> Self {
+        FundsError::OutputError(e)
+    }
+}
 
-        // For route of length 2 we pay 0. (source and destination are included)
-        // For route of length 3 we pay 1.
-        // ...
-        let extra: u128 = if let Some(extra) = length.checked_sub(2) {
-            extra 
-        } else {
-            // This is an invalid route
-            warn!("Received invalid route of length: {}. Skipping route", route_with_capacity.route.len());
-            continue;
+
+/// All possible values for the `--route-strategy` argument
+pub const ROUTE_STRATEGIES: &[&str] = &["cheapest", "shortest", "headroom", "random"];
+
+/// Strategy for choosing a route out of the routes suggested by the index server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteStrategy {
+    /// Lowest fees. Ties are broken in favour of the largest capacity headroom.
+    Cheapest,
+    /// Least amount of hops. Ties are broken by the order of the index server's response.
+    /// (Currently fees are determined by the route length, so this strategy only differs 
+    /// from `Cheapest` in tie breaking)
+    Shortest,
+    /// Largest capacity left on the route after the payment. 
+    /// Such routes are less likely to fail due to concurrent payments.
+    Headroom,
+    /// A random suitable route. Makes it harder for observers to link payments together.
+    Random,
+}
+
+#[derive(Debug)]
+pub struct ParseRouteStrategyError;
+
+impl FromStr for RouteStrategy {
+    type Err = ParseRouteStrategyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cheapest" => Ok(RouteStrategy::Cheapest),
+            "shortest" => Ok(RouteStrategy::Shortest),
+            "headroom" => Ok(RouteStrategy::Headroom),
+            "random" => Ok(RouteStrategy::Random),
+            _ => Err(ParseRouteStrategyError),
+        }
+    }
+}
+
+impl Default for RouteStrategy {
+    fn default() -> Self {
+        RouteStrategy::Cheapest
+    }
+}
+
+/// A route that can be used for pushing a certain amount of credits
+struct SuitableRoute {
+    route: FriendsRoute,
+    fees: u128,
+    /// Capacity left on the route after the payment
+    headroom: u128,
+}
+
+/// Calculate the fees paid along a route. Returns None for invalid routes.
+fn route_fees(route: &FriendsRoute) -> Option<u128> {
+    // TODO: Is this dangerous? How can we do this safely?
+    let length = route.len() as u128;
+
+    // For route of length 2 we pay 0. (source and destination are included)
+    // For route of length 3 we pay 1.
+    // ...
+    let opt_fees = length.checked_sub(2);
+    if opt_fees.is_none() {
+        // This is an invalid route
+        warn!("Received invalid route of length: {}. Skipping route", route.len());
+    }
+    opt_fees
+}
+
+/// Does the route pass through any of the nodes in `avoid`?
+/// Source and destination are not checked.
+fn passes_through(route: &FriendsRoute, avoid: &[PublicKey]) -> bool {
+    let public_keys = &route.public_keys;
+    if public_keys.len() < 2 {
+        return false;
+    }
+    public_keys[1 .. public_keys.len() - 1]
+        .iter()
+        .any(|public_key| avoid.contains(public_key))
+}
+
+/// Filter the routes that have enough capacity for pushing `amount` credits,
+/// and do not pass through any of the nodes in `avoid`.
+fn suitable_routes(routes_with_capacity: Vec<RouteWithCapacity>, 
+                   amount: u128,
+                   avoid: &[PublicKey]) -> Vec<SuitableRoute> {
+
+    let mut suitable_routes = Vec::new();
+    for route_with_capacity in routes_with_capacity {
+        let extra = match route_fees(&route_with_capacity.route) {
+            Some(extra) => extra,
+            None => continue,
         };
 
         let total: u128 = if let Some(total) = extra.checked_add(amount) {
@@ -50,87 +292,1465 @@ fn choose_route(routes_with_capacity: Vec<RouteWithCapacity>, amount: u128)
             continue;
         };
 
-        if total <= route_with_capacity.capacity {
-            return Ok(route_with_capacity.route)
+        if total > route_with_capacity.capacity {
+            continue;
         }
+
+        if passes_through(&route_with_capacity.route, avoid) {
+            continue;
+        }
+
+        suitable_routes.push(SuitableRoute {
+            headroom: route_with_capacity.capacity - total,
+            route: route_with_capacity.route,
+            fees: extra,
+        });
     }
-    Err(FundsError::NoSuitableRoute)
+    suitable_routes
 }
 
-async fn funds_send<'a>(matches: &'a ArgMatches<'a>, 
-                        local_public_key: PublicKey,
-                        mut app_routes: AppRoutes, 
-                        mut app_send_funds: AppSendFunds) -> Result<(), FundsError> {
+/// Choose a route for pushing `amount` credits, according to the given strategy.
+/// Returns the route together with the fees paid along the route.
+fn choose_route(routes_with_capacity: Vec<RouteWithCapacity>, 
+                amount: u128,
+                route_strategy: RouteStrategy,
+                avoid: &[PublicKey]) -> Result<(FriendsRoute, u128), FundsError> {
 
-    let destination_str = matches.value_of("destination").unwrap();
-    let amount_str = matches.value_of("amount").unwrap();
+    let mut suitable_routes = suitable_routes(routes_with_capacity, amount, avoid);
+    if suitable_routes.is_empty() {
+        return Err(FundsError::NoSuitableRoute);
+    }
 
-    let amount = amount_str.parse::<u128>()
-        .map_err(|_| FundsError::ParseAmountError)?;
+    let suitable_route = match route_strategy {
+        RouteStrategy::Cheapest => suitable_routes
+            .into_iter()
+            .min_by_key(|suitable_route| (suitable_route.fees, Reverse(suitable_route.headroom))),
+        RouteStrategy::Shortest => suitable_routes
+            .into_iter()
+            .min_by_key(|suitable_route| suitable_route.route.len()),
+        RouteStrategy::Headroom => suitable_routes
+            .into_iter()
+            .max_by_key(|suitable_route| suitable_route.headroom),
+        RouteStrategy::Random => {
+            let index = rand::thread_rng().gen_range(0, suitable_routes.len());
+            Some(suitable_routes.swap_remove(index))
+        },
+    }.ok_or(FundsError::NoSuitableRoute)?;
 
-    // Destination public key:
-    let destination = string_to_public_key(&destination_str)
-        .map_err(|_| FundsError::InvalidDestination)?;
+    Ok((suitable_route.route, suitable_route.fees))
+}
 
-    // TODO: We might get routes with the exact capacity,
-    // but this will not be enough for sending our amount because
-    // we also need to pay nodes on the way.
-    // We might need to solve this issue at the index server side
-    // (Should the Server take into account the extra credits that should be paid along the way?).
-    let routes_with_capacity = await!(app_routes.request_routes(amount,
-                          local_public_key, // source
-                          destination,
-                          None)) // No exclusion of edges
-        .map_err(|_| FundsError::AppRoutesError)?;
+/// A part of a payment, sent through a single route
+#[derive(Debug, Clone)]
+pub struct PaymentPart {
+    pub route: FriendsRoute,
+    /// Amount of credits received by the destination
+    pub amount: u128,
+    pub fees: u128,
+}
+
+/// Divide a payment of `amount` credits between multiple routes, 
+/// preferring routes with larger capacity (to keep the amount of parts small).
+///
+/// Note that the capacities reported by the index server are calculated for every route 
+/// separately. If routes share edges, some of the parts might fail.
+fn split_payment(routes_with_capacity: Vec<RouteWithCapacity>, 
+                 amount: u128,
+                 avoid: &[PublicKey]) -> Result<Vec<PaymentPart>, FundsError> {
+
+    // (route, fees, maximal amount the route can carry)
+    let mut candidates: Vec<(FriendsRoute, u128, u128)> = Vec::new();
+    for route_with_capacity in routes_with_capacity {
+        let fees = match route_fees(&route_with_capacity.route) {
+            Some(fees) => fees,
+            None => continue,
+        };
+        if passes_through(&route_with_capacity.route, avoid) {
+            continue;
+        }
+        // The same route might be suggested more than once:
+        if candidates.iter().any(|(route, _, _)| route.public_keys == route_with_capacity.route.public_keys) {
+            continue;
+        }
+        match route_with_capacity.capacity.checked_sub(fees) {
+            Some(max_amount) if max_amount > 0 => 
+                candidates.push((route_with_capacity.route, fees, max_amount)),
+            _ => continue,
+        }
+    }
+
+    candidates.sort_by_key(|(_, _, max_amount)| Reverse(*max_amount));
 
-    let route = choose_route(routes_with_capacity, amount)?;
-    let fees = route.len().checked_sub(2).unwrap();
+    let mut payment_parts = Vec::new();
+    let mut remaining = amount;
+    for (route, fees, max_amount) in candidates {
+        if remaining == 0 {
+            break;
+        }
+        let part_amount = max_amount.min(remaining);
+        payment_parts.push(PaymentPart {
+            route,
+            amount: part_amount,
+            fees,
+        });
+        remaining -= part_amount;
+    }
+
+    if remaining > 0 {
+        return Err(FundsError::NoSuitableRoute);
+    }
+    Ok(payment_parts)
+}
+
+/// A user friendly name of a node.
+/// Nodes that are our friends are shown by their names.
+fn public_key_to_name(public_key: &PublicKey, node_report: &NodeReport) -> String {
+    if public_key == &node_report.funder_report.local_public_key {
+        return "(local)".to_owned();
+    }
+    match node_report.funder_report.friends.get(public_key) {
+        Some(friend_report) => friend_report.name.clone(),
+        None => public_key_to_string(public_key),
+    }
+}
+
+/// User friendly names of nodes
+fn public_keys_to_names(public_keys: &[PublicKey], node_report: &NodeReport) -> Vec<String> {
+    public_keys
+        .iter()
+        .map(|public_key| public_key_to_name(public_key, node_report))
+        .collect()
+}
+
+/// User friendly names of the nodes along a route
+fn route_node_names(route: &FriendsRoute, node_report: &NodeReport) -> Vec<String> {
+    public_keys_to_names(&route.public_keys, node_report)
+}
+
+/// A user friendly representation of a route
+fn route_to_string(route: &FriendsRoute, node_report: &NodeReport) -> String {
+    route_node_names(route, node_report).join(" -> ")
+}
+
+/// The payment was completed, but its receipt could not be stored.
+/// Show the receipt, so that it is not lost.
+fn print_unstored_receipt(receipt_file: &ReceiptFile, e: &ReceiptFileError) {
+    warn!("The payment was completed, but its receipt could not be stored: {}", e);
+    match toml::to_string(receipt_file) {
+        Ok(receipt_str) => println!("Receipt:\n{}", receipt_str),
+        Err(_) => println!("Receipt: {:?}", receipt_file),
+    }
+}
+
+/// Show the operations a payment would perform, for `--dry-run`
+fn print_dry_run(payment_parts: &[PaymentPart], 
+                 destination_str: &str,
+                 invoice_id_str: &str) {
+
+    println!("Dry run. The following operations would be performed:");
+    for payment_part in payment_parts {
+        println!("send {} credits to {} through a route of length {} (fees: {}, invoice id: {})",
+                 payment_part.amount, destination_str, 
+                 payment_part.route.len(), payment_part.fees, 
+                 invoice_id_str);
+    }
+}
+
+/// Show the planned payment parts before asking for confirmation
+fn print_payment_plan(payment_parts: &[PaymentPart], 
+                      planned_fees: u128,
+                      amount: u128,
+                      node_report: &NodeReport) -> Result<(), FundsError> {
+
+    let num_parts = payment_parts.len();
+    for (i, payment_part) in payment_parts.iter().enumerate() {
+        if num_parts > 1 {
+            println!("Part {}/{}:", i + 1, num_parts);
+        }
+        println!("Route: {}", route_to_string(&payment_part.route, node_report));
+        println!("Amount: {}", payment_part.amount);
+        println!("Fees: {}", payment_part.fees);
+    }
+    let total = amount.checked_add(planned_fees)
+        .ok_or(FundsError::FeesOverflow)?;
+    println!("Total: {}", total);
+    Ok(())
+}
+
+/// Ask the user a yes/no question
+fn confirm(question: &str) -> Result<bool, FundsError> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush()
+        .map_err(FundsError::ReadConfirmationError)?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)
+        .map_err(FundsError::ReadConfirmationError)?;
+
+    Ok(match answer.trim() {
+        "y" | "Y" | "yes" => true,
+        _ => false,
+    })
+}
+
+/// Store a receipt inside the data directory, and also into `opt_receipt_out` if given.
+/// Returns the path of the receipt file inside the data directory.
+fn store_part_receipt(receipt_file: &ReceiptFile,
+                      data_dir: &Path,
+                      opt_receipt_out: Option<&Path>) -> Result<PathBuf, ReceiptFileError> {
+
+    let receipts_dir = data_dir.join(RECEIPTS_DIR);
+    fs::create_dir_all(&receipts_dir)
+        .map_err(ReceiptFileError::WriteError)?;
+    let receipt_pathbuf = receipts_dir.join(format!("{}.toml", receipt_file.request_id));
+    store_receipt_to_file(receipt_file, &receipt_pathbuf)?;
+    if let Some(receipt_out_path) = opt_receipt_out {
+        store_receipt_to_file(receipt_file, receipt_out_path)?;
+    }
+    Ok(receipt_pathbuf)
+}
+
+/// Send a single payment part, record it in the ledger and store its receipt 
+/// (Also into `opt_receipt_out`, if given).
+/// Returns the receipt, and the path of its file in the data directory 
+/// (Or the reason it could not be stored).
+async fn send_payment_part<'a, S: NodeSendFunds>(app_send_funds: &'a mut S,
+                               payment_part: PaymentPart,
+                               invoice_id: InvoiceId,
+                               destination: &'a PublicKey,
+                               data_dir: &'a Path,
+                               opt_receipt_out: Option<&'a Path>) 
+    -> Result<(ReceiptFile, Result<PathBuf, ReceiptFileError>), FundsError> {
 
-    // A trivial invoice:
     let request_id = gen_uid();
-    let invoice_id = InvoiceId::from(&[0; INVOICE_ID_LEN]);
-    
-    let receipt = await!(app_send_funds.request_send_funds(
+    let route_strings = public_keys_to_strings(&payment_part.route.public_keys);
+    let ledger_pathbuf = data_dir.join(LEDGER_FILE);
+
+    let mut ledger_entry = LedgerEntry {
+        request_id: bytes_to_string(&request_id[..]),
+        timestamp: Utc::now().to_rfc3339(),
+        destination: public_key_to_string(destination),
+        route: route_strings.clone(),
+        amount: payment_part.amount.to_string(),
+        fees: payment_part.fees.to_string(),
+        invoice_id: invoice_id_to_string(&invoice_id),
+        status: LEDGER_STATUS_FAILURE.to_owned(),
+    };
+
+    let receipt = match await!(app_send_funds.request_send_funds(
                               request_id.clone(),
-                              route,
+                              payment_part.route,
                               invoice_id,
-                              amount))
-        .map_err(|_| FundsError::SendFundsError)?;
+                              payment_part.amount)) {
+        Ok(receipt) => receipt,
+        Err(e) => {
+            // Failing to record a failed payment should not hide the original error:
+            if append_to_ledger(&ledger_entry, &ledger_pathbuf).is_err() {
+                warn!("Failed to record a failed payment in the ledger");
+            }
+            // A timed out payment may still complete, so it must not be retried:
+            return Err(node_error(e, FundsError::SendFundsError));
+        },
+    };
 
-    println!("Payment successful!");
-    println!("Fees: {}", fees);
+    let receipt_file = ReceiptFile {
+        request_id: bytes_to_string(&request_id[..]),
+        timestamp: Utc::now().to_rfc3339(),
+        destination: public_key_to_string(destination),
+        route: route_strings,
+        amount: payment_part.amount.to_string(),
+        fees: payment_part.fees.to_string(),
+        response_hash: bytes_to_string(&receipt.response_hash[..]),
+        invoice_id: invoice_id_to_string(&receipt.invoice_id),
+        dest_payment: receipt.dest_payment.to_string(),
+        signature: bytes_to_string(&receipt.signature[..]),
+    };
+
+    // The credits were already moved. The payment is recorded and acknowledged first, 
+    // so that failing to store the receipt can not make it look like a failed payment:
+    ledger_entry.status = LEDGER_STATUS_SUCCESS.to_owned();
+    append_to_ledger(&ledger_entry, &ledger_pathbuf)
+        .map_err(FundsError::StoreLedgerError)?;
 
     await!(app_send_funds.receipt_ack(request_id,
                                       receipt))
-        .map_err(|_| FundsError::ReceiptAckError)
+        .map_err(|e| node_error(e, FundsError::ReceiptAckError))?;
+
+    let store_res = store_part_receipt(&receipt_file, data_dir, opt_receipt_out);
+    Ok((receipt_file, store_res))
+}
+
+/// Find the public keys of friends given their names
+fn friends_public_keys_by_names<'a, I>(node_report: &NodeReport, 
+                                       friend_names: I) -> Result<Vec<PublicKey>, FundsError> 
+where
+    I: Iterator<Item=&'a str>,
+{
+    let mut public_keys = Vec::new();
+    for friend_name in friend_names {
+        let mut opt_friend_public_key = None;
+        for (friend_public_key, friend_report) in &node_report.funder_report.friends {
+            if friend_report.name == friend_name {
+                opt_friend_public_key = Some(friend_public_key.clone());
+            }
+        }
+        public_keys.push(opt_friend_public_key
+                         .ok_or_else(|| FundsError::FriendNameNotFound(friend_name.to_owned()))?);
+    }
+    Ok(public_keys)
+}
+
+/// Load the contacts of the address book inside `data_dir`
+fn load_contacts(data_dir: &Path) -> Result<Vec<Contact>, FundsError> {
+    load_address_book(&data_dir.join(ADDRESS_BOOK_FILE))
+        .map_err(FundsError::LoadAddressBookError)
+}
+
+/// Find the public key of a destination. The destination is tried, in order, as:
+/// - A public key
+/// - The name of a friend
+/// - A path to an existing friend (or node) ticket file
+/// - The name of a contact in the address book inside `opt_data_dir`
+///
+/// The address book (and therefore the data directory) is only required 
+/// if the destination can not be resolved otherwise.
+pub fn resolve_destination(destination_str: &str,
+                           node_report: &NodeReport,
+                           opt_data_dir: Option<&Path>) -> Result<PublicKey, FundsError> {
+
+    if let Ok(public_key) = string_to_public_key(destination_str) {
+        return Ok(public_key);
+    }
+
+    for (friend_public_key, friend_report) in &node_report.funder_report.friends {
+        if friend_report.name == destination_str {
+            return Ok(friend_public_key.clone());
+        }
+    }
+
+    let ticket_pathbuf = PathBuf::from(destination_str);
+    if ticket_pathbuf.is_file() {
+        let friend_address = load_friend_from_file(&ticket_pathbuf)
+            .map_err(|_| FundsError::InvalidDestination(destination_str.to_owned()))?;
+        return Ok(friend_address.public_key);
+    }
+
+    let data_dir = opt_data_dir.ok_or(FundsError::NoDataDir)?;
+    let contacts = load_contacts(data_dir)?;
+    match contact_by_name(&contacts, destination_str) {
+        Some(contact) => Ok(contact.public_key.clone()),
+        None => Err(FundsError::InvalidDestination(destination_str.to_owned())),
+    }
+}
+
+/// Parameters of a single payment
+#[derive(Debug, Clone)]
+pub struct SendFundsParams {
+    pub destination: PublicKey,
+    /// Amount of credits received by the destination (Not including fees)
+    pub amount: u128,
+    pub invoice_id: InvoiceId,
+    pub route_strategy: RouteStrategy,
+    /// Nodes the payment should not pass through
+    pub avoid: Vec<PublicKey>,
+    /// Split the payment between multiple routes if required
+    pub split: bool,
+    pub opt_max_fee: Option<u128>,
+    /// Must be at least 1
+    pub max_attempts: usize,
+    /// No new attempts are started after the deadline
+    pub opt_deadline: Option<Instant>,
+    /// Receipts and the ledger are stored in this directory
+    pub data_dir: PathBuf,
+    /// Additional file for storing the receipt. Can not be used with `split`.
+    pub opt_receipt_out: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttemptStatus {
+    /// The payment parts were not approved, so nothing was paid
+    NotApproved,
+    Success,
+    /// Sending funds failed before any credits were moved
+    Failure(NodeError),
+}
+
+/// A single attempt to pay, through the routes of its payment parts
+#[derive(Debug, Clone)]
+pub struct PaymentAttempt {
+    pub payment_parts: Vec<PaymentPart>,
+    /// Total planned fees of all payment parts
+    pub fees: u128,
+    pub status: AttemptStatus,
+}
+
+/// Outcome of a payment
+#[derive(Debug)]
+pub struct SendFundsOutput {
+    pub fees: u128,
+    /// The receipt of every part of the payment, together with the path of its file 
+    /// (Or the reason it could not be stored. The part was paid either way).
+    /// Empty if the payment was not approved.
+    pub receipts: Vec<(ReceiptFile, Result<PathBuf, ReceiptFileError>)>,
+    /// All attempts, in order. Only the last attempt may have succeeded.
+    pub attempts: Vec<PaymentAttempt>,
 }
 
-pub async fn funds<'a>(matches: &'a ArgMatches<'a>, 
-                       mut node_connection: NodeConnection) -> Result<(), FundsError> {
+/// Send funds according to `params`.
+///
+/// `approve` is called with the planned payment parts and their total fees before anything
+/// is paid (Once for every attempt). If it returns false, nothing is paid.
+pub async fn send_funds<'a, R, S, F>(params: &'a SendFundsParams,
+                        node_report: &'a NodeReport,
+                        app_routes: &'a mut R, 
+                        app_send_funds: &'a mut S,
+                        approve: &'a mut F) -> Result<SendFundsOutput, FundsError> 
+where
+    R: NodeRoutes,
+    S: NodeSendFunds,
+    F: FnMut(&[PaymentPart], u128) -> Result<bool, FundsError>,
+{
+
+    if params.split && params.opt_receipt_out.is_some() {
+        return Err(FundsError::SplitReceiptOut);
+    }
+
+    let mut attempts: Vec<PaymentAttempt> = Vec::new();
+
+    loop {
+        // TODO: We might get routes with the exact capacity,
+        // but this will not be enough for sending our amount because
+        // we also need to pay nodes on the way.
+        // We might need to solve this issue at the index server side
+        // (Should the Server take into account the extra credits that should be paid along the way?).
+        // The node does not tell which hop of a failed route failed. 
+        // We ask the index server to avoid the first hop of the last failed route,
+        // which is the edge our choice of route depended on:
+        let opt_exclude = attempts.last()
+            .and_then(|attempt| attempt.payment_parts.first())
+            .map(|payment_part| &payment_part.route.public_keys)
+            .filter(|public_keys| public_keys.len() >= 2)
+            .map(|public_keys| (public_keys[0].clone(), public_keys[1].clone()));
+
+        let mut routes_with_capacity = await!(app_routes.request_routes(params.amount,
+                              node_report.funder_report.local_public_key.clone(), // source
+                              params.destination.clone(),
+                              opt_exclude))
+            .map_err(|e| node_error(e, FundsError::AppRoutesError))?;
+
+        // Only one edge can be excluded, so routes of earlier attempts are also skipped here:
+        routes_with_capacity.retain(|route_with_capacity| {
+            !attempts
+                .iter()
+                .flat_map(|attempt| attempt.payment_parts.iter())
+                .any(|payment_part| payment_part.route.public_keys == route_with_capacity.route.public_keys)
+        });
+        if !attempts.is_empty() && routes_with_capacity.is_empty() {
+            // No more candidate routes:
+            return Err(FundsError::AttemptsFailed(attempts));
+        }
+
+        // Reject routes that are too expensive:
+        if let Some(max_fee) = params.opt_max_fee {
+            routes_with_capacity.retain(|route_with_capacity| {
+                match route_fees(&route_with_capacity.route) {
+                    Some(fees) => fees <= max_fee,
+                    None => false,
+                }
+            });
+        }
+
+        let plan_res = if params.split {
+            split_payment(routes_with_capacity, params.amount, &params.avoid)
+        } else {
+            choose_route(routes_with_capacity, params.amount, params.route_strategy, &params.avoid)
+                .map(|(route, fees)| vec![PaymentPart {
+                    route,
+                    amount: params.amount,
+                    fees,
+                }])
+        };
+        let payment_parts = match plan_res {
+            Ok(payment_parts) => payment_parts,
+            // The remaining candidate routes are unsuitable. The failed attempts tell more:
+            Err(FundsError::NoSuitableRoute) if !attempts.is_empty() => 
+                return Err(FundsError::AttemptsFailed(attempts)),
+            Err(e) => return Err(e),
+        };
+        let num_parts = payment_parts.len();
+        let mut planned_fees: u128 = 0;
+        for payment_part in &payment_parts {
+            planned_fees = planned_fees.checked_add(payment_part.fees)
+                .ok_or(FundsError::FeesOverflow)?;
+        }
+
+        // The fee limit applies to the total fees of all parts of a split payment:
+        if let Some(max_fee) = params.opt_max_fee {
+            if planned_fees > max_fee {
+                return Err(if attempts.is_empty() {
+                    FundsError::MaxFeeExceeded
+                } else {
+                    FundsError::AttemptsFailed(attempts)
+                });
+            }
+        }
+
+        let mut attempt = PaymentAttempt {
+            payment_parts: payment_parts.clone(),
+            fees: planned_fees,
+            status: AttemptStatus::NotApproved,
+        };
+
+        if !approve(&payment_parts, planned_fees)? {
+            attempts.push(attempt);
+            return Ok(SendFundsOutput {
+                fees: planned_fees,
+                receipts: Vec::new(),
+                attempts,
+            });
+        }
+
+        let mut total_fees: u128 = 0;
+        let mut receipts = Vec::new();
+        let mut opt_failure = None;
+        for (i, payment_part) in payment_parts.into_iter().enumerate() {
+            let part_fees = payment_part.fees;
+            let res = await!(send_payment_part(app_send_funds,
+                                               payment_part,
+                                               params.invoice_id.clone(),
+                                               &params.destination,
+                                               &params.data_dir,
+                                               params.opt_receipt_out.as_ref().map(PathBuf::as_path)));
+
+            let (receipt_file, receipt_store_res) = match res {
+                Ok(res) => res,
+                // Only a failure to send funds (Before any credits were moved)
+                // can be retried:
+                Err(FundsError::SendFundsError(e)) if !params.split => {
+                    opt_failure = Some(e);
+                    break;
+                },
+                Err(e) if num_parts > 1 => 
+                    return Err(FundsError::PaymentPartFailed(i + 1, num_parts, Box::new(e))),
+                Err(e) => return Err(e),
+            };
+            total_fees = total_fees.saturating_add(part_fees);
+            receipts.push((receipt_file, receipt_store_res));
+        }
+
+        if let Some(e) = opt_failure {
+            attempt.status = AttemptStatus::Failure(e);
+            attempts.push(attempt);
+
+            let deadline_passed = params.opt_deadline
+                .map(|deadline| Instant::now() >= deadline)
+                .unwrap_or(false);
+            if attempts.len() >= params.max_attempts || deadline_passed {
+                return Err(FundsError::AttemptsFailed(attempts));
+            }
+            continue;
+        }
+
+        attempt.status = AttemptStatus::Success;
+        attempts.push(attempt);
+        return Ok(SendFundsOutput {
+            fees: total_fees,
+            receipts,
+            attempts,
+        });
+    }
+}
+
+/// Show the failed attempts of a payment
+fn print_failed_attempts(attempts: &[PaymentAttempt], 
+                         max_attempts: usize,
+                         node_report: &NodeReport) {
+    for (i, attempt) in attempts.iter().enumerate() {
+        if let AttemptStatus::Failure(e) = &attempt.status {
+            for payment_part in &attempt.payment_parts {
+                println!("Attempt {}/{} failed: {}. Route: {}", 
+                         i + 1, max_attempts, e, route_to_string(&payment_part.route, node_report));
+            }
+        }
+    }
+}
+
+async fn funds_send<'a, R: NodeRoutes, S: NodeSendFunds>(matches: &'a ArgMatches<'a>, 
+                        profile: &'a Profile,
+                        node_report: &'a NodeReport,
+                        mut app_routes: R, 
+                        mut app_send_funds: S) -> Result<(), FundsError> {
+
+    let opt_data_dir = data_dir(matches, profile);
+
+    let route_strategy = matches.value_of("route_strategy")
+        // Possible values are checked by clap:
+        .map(|route_strategy_str| route_strategy_str.parse().unwrap())
+        .unwrap_or_default();
+
+    let avoid = friends_public_keys_by_names(node_report,
+                                             matches.values_of("avoid_friend").into_iter().flatten())?;
+
+    let is_split = matches.is_present("split");
+
+    let opt_max_fee = match matches.value_of("max_fee") {
+        Some(max_fee_str) => Some(max_fee_str.parse::<u128>()
+            .map_err(|_| FundsError::ParseMaxFeeError(max_fee_str.to_owned()))?),
+        None => None,
+    };
+
+    let (destination, amount, invoice_id) = match matches.value_of("invoice_file") {
+        Some(invoice_file) => {
+            let invoice_pathbuf = PathBuf::from(invoice_file);
+            if !invoice_pathbuf.exists() {
+                return Err(FundsError::InvoiceFileNotFound(invoice_pathbuf));
+            }
+            let invoice = load_invoice_from_file(&invoice_pathbuf)
+                .map_err(FundsError::LoadInvoiceError)?;
+            if let Some(memo) = &invoice.memo {
+                println!("Invoice memo: {}", memo);
+            }
+            (invoice.destination, invoice.amount, invoice.invoice_id)
+        },
+        None => {
+            let destination_str = matches.value_of("destination").unwrap();
+            let amount_str = matches.value_of("amount").unwrap();
+
+            let amount = amount_str.parse::<u128>()
+                .map_err(|_| FundsError::ParseAmountError(amount_str.to_owned()))?;
+
+            // Destination public key:
+            let destination = resolve_destination(destination_str, 
+                                                  node_report, 
+                                                  opt_data_dir.as_ref().map(PathBuf::as_path))?;
+
+            let invoice_id = if is_split {
+                // The parts of a split payment share a random invoice id, 
+                // to allow the destination to match them together:
+                let mut invoice_id_array = [0u8; INVOICE_ID_LEN];
+                rand::thread_rng().fill_bytes(&mut invoice_id_array);
+                InvoiceId::from(&invoice_id_array)
+            } else {
+                // A trivial invoice:
+                InvoiceId::from(&[0; INVOICE_ID_LEN])
+            };
+            (destination, amount, invoice_id)
+        },
+    };
+
+    // Make sure we can store the receipt before we pay:
+    let opt_receipt_out_pathbuf = matches.value_of("receipt_out").map(PathBuf::from);
+    if let Some(receipt_out_pathbuf) = &opt_receipt_out_pathbuf {
+        if receipt_out_pathbuf.exists() {
+            return Err(FundsError::OutputFileAlreadyExists(receipt_out_pathbuf.clone()));
+        }
+    }
+    let data_dir = opt_data_dir
+        .ok_or(FundsError::NoDataDir)?;
+
+    let max_attempts = match matches.value_of("attempts") {
+        Some(attempts_str) => attempts_str.parse::<usize>()
+            .map_err(|_| FundsError::ParseAttemptsError(attempts_str.to_owned()))?,
+        None => 1,
+    };
+    if max_attempts == 0 {
+        return Err(FundsError::ParseAttemptsError(max_attempts.to_string()));
+    }
+
+    let opt_deadline = match matches.value_of("deadline") {
+        Some(deadline_str) => {
+            let deadline_secs = deadline_str.parse::<u64>()
+                .map_err(|_| FundsError::ParseDeadlineError(deadline_str.to_owned()))?;
+            Some(Instant::now() + Duration::from_secs(deadline_secs))
+        },
+        None => None,
+    };
+
+    let params = SendFundsParams {
+        destination,
+        amount,
+        invoice_id,
+        route_strategy,
+        avoid,
+        split: is_split,
+        opt_max_fee,
+        max_attempts,
+        opt_deadline,
+        data_dir,
+        opt_receipt_out: opt_receipt_out_pathbuf,
+    };
+
+    let dry_run = matches.is_present("dry_run");
+    let is_confirm = matches.is_present("confirm");
+    let mut approve = |payment_parts: &[PaymentPart], planned_fees: u128| {
+        if dry_run {
+            return Ok(false);
+        }
+        if is_confirm {
+            print_payment_plan(payment_parts, planned_fees, params.amount, node_report)?;
+            if !confirm("Send payment?")? {
+                return Err(FundsError::PaymentNotConfirmed);
+            }
+        }
+        Ok(true)
+    };
+
+    let res = await!(send_funds(&params, 
+                                node_report, 
+                                &mut app_routes, 
+                                &mut app_send_funds,
+                                &mut approve));
+    match &res {
+        Ok(send_funds_output) => 
+            print_failed_attempts(&send_funds_output.attempts, max_attempts, node_report),
+        Err(FundsError::AttemptsFailed(attempts)) => 
+            print_failed_attempts(attempts, max_attempts, node_report),
+        Err(_) => {},
+    }
+    let send_funds_output = res?;
+
+    if dry_run {
+        if let Some(attempt) = send_funds_output.attempts.last() {
+            print_dry_run(&attempt.payment_parts, 
+                          &public_key_to_string(&params.destination), 
+                          &invoice_id_to_string(&params.invoice_id));
+        }
+        return Ok(());
+    }
+
+    let num_parts = send_funds_output.receipts.len();
+    for (i, (receipt_file, receipt_store_res)) in send_funds_output.receipts.iter().enumerate() {
+        if num_parts > 1 {
+            println!("Part {}/{}: sent {} credits (fees: {})", 
+                     i + 1, num_parts, receipt_file.amount, receipt_file.fees);
+        }
+        match receipt_store_res {
+            Ok(receipt_pathbuf) => println!("Receipt: {}", receipt_pathbuf.display()),
+            Err(e) => print_unstored_receipt(receipt_file, e),
+        }
+    }
+    println!("Payment successful!");
+    println!("Fees: {}", send_funds_output.fees);
+    Ok(())
+}
+
+/// Resolve a batch payment into its destination, amount and invoice id
+fn resolve_batch_payment(node_report: &NodeReport,
+                         data_dir: &Path,
+                         batch_payment: &BatchPayment) -> Option<(PublicKey, u128, InvoiceId)> {
+    let destination = resolve_destination(&batch_payment.destination, 
+                                          node_report, 
+                                          Some(data_dir)).ok()?;
+    let amount = batch_payment.amount.parse::<u128>().ok()?;
+    let invoice_id = match &batch_payment.invoice_id {
+        Some(invoice_id_str) => string_to_invoice_id(invoice_id_str).ok()?,
+        // A trivial invoice:
+        None => InvoiceId::from(&[0; INVOICE_ID_LEN]),
+    };
+    Some((destination, amount, invoice_id))
+}
+
+/// Parameters of a batch of payments
+#[derive(Debug, Clone)]
+pub struct SendBatchParams {
+    pub batch_payments: Vec<BatchPayment>,
+    pub route_strategy: RouteStrategy,
+    /// Nodes the payments should not pass through
+    pub avoid: Vec<PublicKey>,
+    /// Max fee of every payment
+    pub opt_max_fee: Option<u128>,
+    /// Attempts of every payment. Must be at least 1
+    pub max_attempts: usize,
+    /// Only plan the payments, without paying
+    pub dry_run: bool,
+    /// Receipts and the ledger are stored in this directory
+    pub data_dir: PathBuf,
+    /// The outcome of every payment is written to this file. Must not exist.
+    pub results_path: PathBuf,
+}
+
+/// Outcome of a batch of payments
+#[derive(Debug)]
+pub struct SendBatchOutput {
+    /// The outcome of every payment, as written to the results file
+    pub results: Vec<BatchResult>,
+    /// Amount of failed payments
+    pub num_failed: usize,
+}
+
+/// Pay a batch of payments, one after the other.
+/// A failed payment does not stop the batch. The outcome of every payment 
+/// is written to the results file.
+///
+/// `on_payment` is called with the index and the outcome of every payment, once it is done.
+pub async fn send_batch<'a, R, S, F>(params: &'a SendBatchParams,
+                        node_report: &'a NodeReport,
+                        app_routes: &'a mut R, 
+                        app_send_funds: &'a mut S,
+                        on_payment: &'a mut F) -> Result<SendBatchOutput, FundsError> 
+where
+    R: NodeRoutes,
+    S: NodeSendFunds,
+    F: FnMut(usize, &Result<SendFundsOutput, FundsError>),
+{
+
+    if params.results_path.exists() {
+        return Err(FundsError::OutputFileAlreadyExists(params.results_path.clone()));
+    }
+
+    // Validate the whole batch before paying anything.
+    // A dry run previews all rows, recording invalid rows in the results instead.
+    let mut params_list = Vec::new();
+    for (i, batch_payment) in params.batch_payments.iter().enumerate() {
+        let (destination, amount, invoice_id) = match resolve_batch_payment(node_report, &params.data_dir, batch_payment) {
+            Some(resolved) => resolved,
+            // Rows are numbered from 1:
+            None if params.dry_run => {
+                params_list.push(Err(FundsError::InvalidBatchPayment(i + 1)));
+                continue;
+            },
+            None => return Err(FundsError::InvalidBatchPayment(i + 1)),
+        };
+        params_list.push(Ok(SendFundsParams {
+            destination,
+            amount,
+            invoice_id,
+            route_strategy: params.route_strategy,
+            avoid: params.avoid.clone(),
+            split: false,
+            opt_max_fee: params.opt_max_fee,
+            max_attempts: params.max_attempts,
+            // Every payment gets its own attempts, without a deadline:
+            opt_deadline: None,
+            data_dir: params.data_dir.clone(),
+            opt_receipt_out: None,
+        }));
+    }
+
+    let dry_run = params.dry_run;
+    let mut batch_results = Vec::new();
+    let mut num_failed: usize = 0;
+    for (i, (batch_payment, params_res)) in params.batch_payments.iter().zip(params_list).enumerate() {
+        let res = match params_res {
+            Ok(send_funds_params) => {
+                let mut approve = |_payment_parts: &[PaymentPart], _planned_fees: u128| Ok(!dry_run);
+                await!(send_funds(&send_funds_params, 
+                                  node_report, 
+                                  app_routes, 
+                                  app_send_funds,
+                                  &mut approve))
+            },
+            Err(e) => Err(e),
+        };
+
+        let batch_result = match &res {
+            Ok(send_funds_output) => BatchResult {
+                destination: batch_payment.destination.clone(),
+                amount: batch_payment.amount.clone(),
+                memo: batch_payment.memo.clone(),
+                status: if dry_run { "planned" } else { "success" }.to_owned(),
+                fees: Some(send_funds_output.fees.to_string()),
+                receipt: send_funds_output.receipts
+                    .first()
+                    .and_then(|(_, receipt_store_res)| receipt_store_res.as_ref().ok())
+                    .map(|receipt_pathbuf| receipt_pathbuf.display().to_string()),
+                // The payment succeeded, but a receipt could not be stored:
+                error: send_funds_output.receipts
+                    .iter()
+                    .filter_map(|(_, receipt_store_res)| receipt_store_res.as_ref().err())
+                    .next()
+                    .map(|e| format!("paid, but the receipt was not stored: {}", e)),
+            },
+            Err(e) => {
+                num_failed += 1;
+                BatchResult {
+                    destination: batch_payment.destination.clone(),
+                    amount: batch_payment.amount.clone(),
+                    memo: batch_payment.memo.clone(),
+                    status: "failure".to_owned(),
+                    fees: None,
+                    receipt: None,
+                    error: Some(e.to_string()),
+                }
+            },
+        };
+        batch_results.push(batch_result);
+
+        // The results file is rewritten after every payment, so that it remains
+        // accurate if the batch is interrupted:
+        store_batch_results_to_file(&batch_results, &params.results_path)
+            .map_err(FundsError::StoreBatchResultsError)?;
+
+        on_payment(i, &res);
+    }
+
+    // Make sure a results file exists, even for an empty batch:
+    if batch_results.is_empty() {
+        store_batch_results_to_file(&batch_results, &params.results_path)
+            .map_err(FundsError::StoreBatchResultsError)?;
+    }
+
+    Ok(SendBatchOutput {
+        results: batch_results,
+        num_failed,
+    })
+}
+
+async fn funds_send_batch<'a, R: NodeRoutes, S: NodeSendFunds>(matches: &'a ArgMatches<'a>, 
+                              profile: &'a Profile,
+                              node_report: &'a NodeReport,
+                              mut app_routes: R, 
+                              mut app_send_funds: S) -> Result<(), FundsError> {
+
+    let batch_pathbuf = PathBuf::from(matches.value_of("batch_file").unwrap());
+    if !batch_pathbuf.exists() {
+        return Err(FundsError::BatchFileNotFound(batch_pathbuf));
+    }
+    let batch_payments = load_batch_from_file(&batch_pathbuf)
+        .map_err(FundsError::LoadBatchError)?;
+
+    let route_strategy = matches.value_of("route_strategy")
+        // Possible values are checked by clap:
+        .map(|route_strategy_str| route_strategy_str.parse().unwrap())
+        .unwrap_or_default();
+
+    let avoid = friends_public_keys_by_names(node_report,
+                                             matches.values_of("avoid_friend").into_iter().flatten())?;
+
+    let opt_max_fee = match matches.value_of("max_fee") {
+        Some(max_fee_str) => Some(max_fee_str.parse::<u128>()
+            .map_err(|_| FundsError::ParseMaxFeeError(max_fee_str.to_owned()))?),
+        None => None,
+    };
+
+    let max_attempts = match matches.value_of("attempts") {
+        Some(attempts_str) => attempts_str.parse::<usize>()
+            .map_err(|_| FundsError::ParseAttemptsError(attempts_str.to_owned()))?,
+        None => 1,
+    };
+    if max_attempts == 0 {
+        return Err(FundsError::ParseAttemptsError(max_attempts.to_string()));
+    }
+
+    let data_dir = data_dir(matches, profile)
+        .ok_or(FundsError::NoDataDir)?;
+
+    let params = SendBatchParams {
+        batch_payments,
+        route_strategy,
+        avoid,
+        opt_max_fee,
+        max_attempts,
+        dry_run: matches.is_present("dry_run"),
+        data_dir,
+        results_path: PathBuf::from(matches.value_of("output_file").unwrap()),
+    };
+
+    let num_payments = params.batch_payments.len();
+    let mut on_payment = |i: usize, res: &Result<SendFundsOutput, FundsError>| {
+        let batch_payment = &params.batch_payments[i];
+        println!("Payment {}/{}: {} credits to {}", 
+                 i + 1, num_payments, batch_payment.amount, batch_payment.destination);
+        match res {
+            Ok(send_funds_output) => {
+                print_failed_attempts(&send_funds_output.attempts, max_attempts, node_report);
+                if params.dry_run {
+                    if let Some(attempt) = send_funds_output.attempts.last() {
+                        let invoice_id_str = match &batch_payment.invoice_id {
+                            Some(invoice_id_str) => invoice_id_str.clone(),
+                            None => invoice_id_to_string(&InvoiceId::from(&[0; INVOICE_ID_LEN])),
+                        };
+                        print_dry_run(&attempt.payment_parts, &batch_payment.destination, &invoice_id_str);
+                    }
+                }
+            },
+            Err(e) => {
+                if let FundsError::AttemptsFailed(attempts) = e {
+                    print_failed_attempts(attempts, max_attempts, node_report);
+                }
+                println!("Payment {}/{} failed: {}", i + 1, num_payments, e);
+            },
+        }
+    };
+
+    let send_batch_output = await!(send_batch(&params, 
+                                              node_report, 
+                                              &mut app_routes, 
+                                              &mut app_send_funds,
+                                              &mut on_payment))?;
+
+    let done_str = if params.dry_run { "can be sent" } else { "succeeded" };
+    println!("{}/{} payments {}. Results: {}", 
+             num_payments - send_batch_output.num_failed, num_payments, done_str, 
+             params.results_path.display());
+    if send_batch_output.num_failed > 0 {
+        return Err(FundsError::BatchPaymentsFailed(send_batch_output.num_failed));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct RouteInfo {
+    pub hops: usize,
+    /// None for invalid routes
+    pub fees: Option<String>,
+    pub capacity: String,
+    /// Route nodes, including source and destination.
+    /// Our friends are shown by their names.
+    pub nodes: Vec<String>,
+    pub public_keys: Vec<String>,
+}
+
+/// Get all routes suggested by the index server for sending `amount` credits to `destination`
+pub async fn routes_info<'a, R: NodeRoutes>(app_routes: &'a mut R, 
+                         node_report: &'a NodeReport,
+                         destination: PublicKey,
+                         amount: u128) -> Result<Vec<RouteInfo>, FundsError> {
+
+    let local_public_key = node_report.funder_report.local_public_key.clone();
+    let routes_with_capacity = await!(app_routes.request_routes(amount,
+                          local_public_key, // source
+                          destination,
+                          None)) // No exclusion of edges
+        .map_err(|e| node_error(e, FundsError::AppRoutesError))?;
+
+    Ok(routes_with_capacity
+        .iter()
+        .map(|route_with_capacity| {
+            let route = &route_with_capacity.route;
+            RouteInfo {
+                hops: route.len().saturating_sub(1),
+                fees: route_fees(route).map(|fees| fees.to_string()),
+                capacity: route_with_capacity.capacity.to_string(),
+                nodes: route_node_names(route, node_report),
+                public_keys: public_keys_to_strings(&route.public_keys),
+            }
+        })
+        .collect())
+}
+
+/// Show all routes suggested by the index server for sending funds to a destination,
+/// without paying.
+async fn funds_routes<'a, R: NodeRoutes>(matches: &'a ArgMatches<'a>, 
+                          profile: &'a Profile,
+                          node_report: &'a NodeReport,
+                          mut app_routes: R) -> Result<(), FundsError> {
+
+    let destination_str = matches.value_of("destination").unwrap();
+    let amount_str = matches.value_of("amount").unwrap();
+    let format = OutputFormat::from_matches(matches, profile);
+
+    let amount = amount_str.parse::<u128>()
+        .map_err(|_| FundsError::ParseAmountError(amount_str.to_owned()))?;
+
+    let opt_data_dir = data_dir(matches, profile);
+    let destination = resolve_destination(destination_str, 
+                                          node_report, 
+                                          opt_data_dir.as_ref().map(PathBuf::as_path))?;
+
+    let routes_info = await!(routes_info(&mut app_routes, node_report, destination, amount))?;
+
+    let mut table = Table::new();
+    // Add title:
+    table.add_row(row!["#",
+                       "hops",
+                       "fees",
+                       "capacity",
+                       "route"]);
+
+    for (i, route_info) in routes_info.iter().enumerate() {
+        table.add_row(row![i,
+                           route_info.hops,
+                           route_info.fees.clone().unwrap_or_else(|| "invalid".to_owned()),
+                           route_info.capacity,
+                           route_info.nodes.join("\n")]);
+    }
+
+    print_output(format, "routes", &table, &routes_info, &routes_info)?;
+    Ok(())
+}
+
+/// Parse a date given either in RFC 3339 format or as `YYYY-MM-DD` (Midnight, UTC)
+fn parse_date(date_str: &str) -> Result<DateTime<Utc>, FundsError> {
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(date_str) {
+        return Ok(date_time.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(date_str, "%Y-%m-%d")
+        .map_err(|_| FundsError::ParseDateError(date_str.to_owned()))?;
+    Ok(DateTime::<Utc>::from_utc(date.and_hms(0, 0, 0), Utc))
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryInfo {
+    pub entries: Vec<LedgerEntry>,
+    /// Amount of successful payments
+    pub num_success: usize,
+    /// Total credits sent in successful payments (Not including fees)
+    pub total_amount: String,
+    /// Total fees paid in successful payments
+    pub total_fees: String,
+}
+
+/// Filter for the entries of the ledger. Empty fields match every entry.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    /// Only entries at this time or later
+    pub opt_since: Option<DateTime<Utc>>,
+    /// Only entries before this time
+    pub opt_until: Option<DateTime<Utc>>,
+    pub opt_destination: Option<PublicKey>,
+    /// `success` or `failure`
+    pub opt_status: Option<String>,
+}
+
+/// Select the ledger entries matching `filter`, and sum up the successful payments
+pub fn history_info(ledger_entries: Vec<LedgerEntry>, 
+                    filter: &HistoryFilter) -> HistoryInfo {
+
+    let mut entries = Vec::new();
+    let mut total_amount: u128 = 0;
+    let mut total_fees: u128 = 0;
+    let mut num_success: usize = 0;
+    for ledger_entry in ledger_entries {
+        if filter.opt_since.is_some() || filter.opt_until.is_some() {
+            let timestamp = match DateTime::parse_from_rfc3339(&ledger_entry.timestamp) {
+                Ok(timestamp) => timestamp.with_timezone(&Utc),
+                Err(_) => {
+                    warn!("Invalid ledger timestamp: {}. Skipping entry", ledger_entry.timestamp);
+                    continue;
+                },
+            };
+            if filter.opt_since.map(|since| timestamp < since).unwrap_or(false) 
+                || filter.opt_until.map(|until| timestamp >= until).unwrap_or(false) {
+                continue;
+            }
+        }
+
+        if let Some(destination) = &filter.opt_destination {
+            if string_to_public_key(&ledger_entry.destination).ok().as_ref() != Some(destination) {
+                continue;
+            }
+        }
+
+        if let Some(status) = &filter.opt_status {
+            if &ledger_entry.status != status {
+                continue;
+            }
+        }
+
+        if ledger_entry.status == LEDGER_STATUS_SUCCESS {
+            num_success += 1;
+            total_amount = total_amount.saturating_add(
+                ledger_entry.amount.parse::<u128>().unwrap_or(0));
+            total_fees = total_fees.saturating_add(
+                ledger_entry.fees.parse::<u128>().unwrap_or(0));
+        }
+        entries.push(ledger_entry);
+    }
+
+    HistoryInfo {
+        entries,
+        num_success,
+        total_amount: total_amount.to_string(),
+        total_fees: total_fees.to_string(),
+    }
+}
+
+/// Load the ledger inside `data_dir`, and select the entries matching `filter`
+pub fn load_history(data_dir: &Path, filter: &HistoryFilter) -> Result<HistoryInfo, FundsError> {
+    let ledger_entries = load_ledger(&data_dir.join(LEDGER_FILE))
+        .map_err(FundsError::LoadLedgerError)?;
+    Ok(history_info(ledger_entries, filter))
+}
+
+/// Show the payments recorded in the local ledger
+fn funds_history<'a>(matches: &'a ArgMatches<'a>, 
+                     profile: &'a Profile,
+                     node_report: &'a NodeReport) -> Result<(), FundsError> {
+
+    let format = OutputFormat::from_matches(matches, profile);
+    let opt_data_dir = data_dir(matches, profile);
+
+    let opt_since = match matches.value_of("since") {
+        Some(since_str) => Some(parse_date(since_str)?),
+        None => None,
+    };
+    let opt_until = match matches.value_of("until") {
+        Some(until_str) => Some(parse_date(until_str)?),
+        None => None,
+    };
+    let opt_destination = match matches.value_of("destination") {
+        Some(destination_str) => Some(resolve_destination(destination_str, 
+                                                          node_report, 
+                                                          opt_data_dir.as_ref().map(PathBuf::as_path))?),
+        None => None,
+    };
+    let filter = HistoryFilter {
+        opt_since,
+        opt_until,
+        opt_destination,
+        opt_status: matches.value_of("status").map(|status| status.to_owned()),
+    };
+
+    let data_dir = opt_data_dir
+        .ok_or(FundsError::NoDataDir)?;
+    let history_info = load_history(&data_dir, &filter)?;
+
+    let mut table = Table::new();
+    // Add title:
+    table.add_row(row!["time",
+                       "destination",
+                       "amount",
+                       "fees",
+                       "status",
+                       "invoice id"]);
+
+    for ledger_entry in &history_info.entries {
+        let destination_name = match string_to_public_key(&ledger_entry.destination) {
+            Ok(entry_destination) => public_key_to_name(&entry_destination, node_report),
+            Err(_) => ledger_entry.destination.clone(),
+        };
+        table.add_row(row![ledger_entry.timestamp,
+                           destination_name,
+                           ledger_entry.amount,
+                           ledger_entry.fees,
+                           ledger_entry.status,
+                           ledger_entry.invoice_id]);
+    }
+
+    print_output(format, "history", &table, &history_info, &history_info.entries)?;
+    if format == OutputFormat::Table {
+        println!("Successful payments: {}", history_info.num_success);
+        println!("Total amount: {}", history_info.total_amount);
+        println!("Total fees: {}", history_info.total_fees);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContactInfo {
+    pub name: String,
+    pub public_key: String,
+}
+
+/// List the contacts of the address book inside `data_dir`
+pub fn list_contacts(data_dir: &Path) -> Result<Vec<ContactInfo>, FundsError> {
+    Ok(load_contacts(data_dir)?
+        .into_iter()
+        .map(|contact| ContactInfo {
+            public_key: public_key_to_string(&contact.public_key),
+            name: contact.name,
+        })
+        .collect())
+}
+
+/// Add a contact to the address book inside `data_dir`. Contact names are unique.
+pub fn add_contact(data_dir: &Path, contact: Contact) -> Result<(), FundsError> {
+    let mut contacts = load_contacts(data_dir)?;
+    if contact_by_name(&contacts, &contact.name).is_some() {
+        return Err(FundsError::DuplicateContactName(contact.name));
+    }
+    contacts.push(contact);
+    store_address_book(&contacts, &data_dir.join(ADDRESS_BOOK_FILE))
+        .map_err(FundsError::StoreAddressBookError)
+}
+
+/// Remove a contact from the address book inside `data_dir`
+pub fn remove_contact(data_dir: &Path, name: &str) -> Result<(), FundsError> {
+    let mut contacts = load_contacts(data_dir)?;
+    let num_contacts = contacts.len();
+    contacts.retain(|contact| contact.name != name);
+    if contacts.len() == num_contacts {
+        return Err(FundsError::ContactNameNotFound(name.to_owned()));
+    }
+    store_address_book(&contacts, &data_dir.join(ADDRESS_BOOK_FILE))
+        .map_err(FundsError::StoreAddressBookError)
+}
+
+/// Manage the local address book
+fn funds_contacts<'a>(matches: &'a ArgMatches<'a>, 
+                      profile: &'a Profile,
+                      node_report: &'a NodeReport) -> Result<(), FundsError> {
+
+    let data_dir = data_dir(matches, profile)
+        .ok_or(FundsError::NoDataDir)?;
+
+    match matches.subcommand() {
+        ("add", Some(matches)) => {
+            let name = matches.value_of("contact_name").unwrap();
+            let destination_str = matches.value_of("destination").unwrap();
+            let public_key = resolve_destination(destination_str, node_report, Some(&data_dir))?;
+            if matches.is_present("dry_run") {
+                if contact_by_name(&load_contacts(&data_dir)?, name).is_some() {
+                    return Err(FundsError::DuplicateContactName(name.to_owned()));
+                }
+                println!("Dry run. Would add contact {:?} to {}", 
+                         name, data_dir.join(ADDRESS_BOOK_FILE).display());
+                return Ok(());
+            }
+            add_contact(&data_dir, Contact {
+                name: name.to_owned(),
+                public_key,
+            })
+        },
+        ("remove", Some(matches)) => {
+            let name = matches.value_of("contact_name").unwrap();
+            if matches.is_present("dry_run") {
+                if contact_by_name(&load_contacts(&data_dir)?, name).is_none() {
+                    return Err(FundsError::ContactNameNotFound(name.to_owned()));
+                }
+                println!("Dry run. Would remove contact {:?} from {}", 
+                         name, data_dir.join(ADDRESS_BOOK_FILE).display());
+                return Ok(());
+            }
+            remove_contact(&data_dir, name)
+        },
+        ("list", Some(matches)) => {
+            let format = OutputFormat::from_matches(matches, profile);
+            let contacts_info = list_contacts(&data_dir)?;
+
+            let mut table = Table::new();
+            // Add title:
+            table.add_row(row!["name", "public key"]);
+            for contact_info in &contacts_info {
+                table.add_row(row![contact_info.name, contact_info.public_key]);
+            }
+
+            print_output(format, "contacts", &table, &contacts_info, &contacts_info)?;
+            Ok(())
+        },
+        _ => unreachable!(),
+    }
+}
+
+/// Create an invoice with a random invoice id
+pub fn new_invoice(destination: PublicKey, amount: u128, opt_memo: Option<String>) -> Invoice {
+    let mut invoice_id_array = [0u8; INVOICE_ID_LEN];
+    rand::thread_rng().fill_bytes(&mut invoice_id_array);
+
+    Invoice {
+        invoice_id: InvoiceId::from(&invoice_id_array),
+        destination,
+        amount,
+        memo: opt_memo,
+    }
+}
+
+/// Parameters for creating an invoice file
+#[derive(Debug, Clone)]
+pub struct CreateInvoiceParams {
+    /// The receiver of the funds
+    pub destination: PublicKey,
+    pub amount: u128,
+    pub opt_memo: Option<String>,
+    /// Must not exist
+    pub output_path: PathBuf,
+}
+
+/// Create an invoice file, to be paid using `funds send --invoice`
+pub fn create_invoice(params: &CreateInvoiceParams) -> Result<Invoice, FundsError> {
+    if params.output_path.exists() {
+        return Err(FundsError::OutputFileAlreadyExists(params.output_path.clone()));
+    }
+
+    let invoice = new_invoice(params.destination.clone(), params.amount, params.opt_memo.clone());
+
+    store_invoice_to_file(&invoice, &params.output_path)
+        .map_err(FundsError::StoreInvoiceError)?;
+
+    Ok(invoice)
+}
+
+fn funds_create_invoice<'a>(matches: &'a ArgMatches<'a>, 
+                            profile: &'a Profile,
+                            node_report: &'a NodeReport) -> Result<(), FundsError> {
+
+    let amount_str = matches.value_of("amount").unwrap();
+    let amount = amount_str.parse::<u128>()
+        .map_err(|_| FundsError::ParseAmountError(amount_str.to_owned()))?;
+
+    // By default, the invoice is paid to us:
+    let destination = match matches.value_of("destination") {
+        Some(destination_str) => {
+            let opt_data_dir = data_dir(matches, profile);
+            resolve_destination(destination_str, 
+                                node_report, 
+                                opt_data_dir.as_ref().map(PathBuf::as_path))?
+        },
+        None => node_report.funder_report.local_public_key.clone(),
+    };
+
+    let params = CreateInvoiceParams {
+        destination,
+        amount,
+        opt_memo: matches.value_of("memo").map(|memo| memo.to_owned()),
+        output_path: PathBuf::from(matches.value_of("output_file").unwrap()),
+    };
+
+    if matches.is_present("dry_run") {
+        if params.output_path.exists() {
+            return Err(FundsError::OutputFileAlreadyExists(params.output_path));
+        }
+        println!("Dry run. Would write an invoice to {}", params.output_path.display());
+        return Ok(());
+    }
+
+    let invoice = create_invoice(&params)?;
+
+    println!("Invoice id: {}", invoice_id_to_string(&invoice.invoice_id));
+    Ok(())
+}
+
+pub async fn funds<'a, N: Node>(matches: &'a ArgMatches<'a>, 
+                       profile: &'a Profile,
+                       node_connection: &'a mut N) -> Result<(), FundsError> {
 
     // Get our local public key:
     let mut app_report = node_connection.report().clone();
     let (node_report, incoming_mutations) = await!(app_report.incoming_reports())
-        .map_err(|_| FundsError::GetReportError)?;
+        .map_err(|e| node_error(e, FundsError::GetReportError))?;
     // We currently don't need live updates about report mutations:
     drop(incoming_mutations);
 
-    let local_public_key = node_report.funder_report.local_public_key.clone();
+    // Creating an invoice does not require any permissions:
+    if let ("create-invoice", Some(matches)) = matches.subcommand() {
+        return funds_create_invoice(matches, profile, &node_report);
+    }
 
-    let app_send_funds = node_connection.send_funds()
-        .ok_or(FundsError::NoFundsPermissions)?
-        .clone();
+    // The address book is local, so no permissions are required:
+    if let ("contacts", Some(matches)) = matches.subcommand() {
+        return funds_contacts(matches, profile, &node_report);
+    }
+
+    // The ledger is local, so no permissions are required:
+    if let ("history", Some(matches)) = matches.subcommand() {
+        return funds_history(matches, profile, &node_report);
+    }
 
     let app_routes = node_connection.routes()
         .ok_or(FundsError::NoRoutesPermissions)?
         .clone();
 
+    // Inspecting routes only requires routes permissions:
+    if let ("routes", Some(matches)) = matches.subcommand() {
+        return await!(funds_routes(matches, profile, &node_report, app_routes));
+    }
+
+    let app_send_funds = node_connection.send_funds()
+        .ok_or(FundsError::NoFundsPermissions)?
+        .clone();
 
     match matches.subcommand() {
         ("send", Some(matches)) => await!(funds_send(matches, 
-                                                     local_public_key, 
+                                                     profile,
+                                                     &node_report, 
                                                      app_routes, 
                                                      app_send_funds))?,
+        ("send-batch", Some(matches)) => await!(funds_send_batch(matches, 
+                                                                 profile,
+                                                                 &node_report, 
+                                                                 app_routes, 
+                                                                 app_send_funds))?,
         _ => unreachable!(),
     }
 
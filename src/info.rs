@@ -1,37 +1,160 @@
-use std::path::PathBuf;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::time;
+
+use futures::StreamExt;
+
+use chrono::{DateTime, Duration, Utc};
 
 use clap::ArgMatches;
 use prettytable::Table;
 
-use app::{NodeConnection, AppReport, public_key_to_string,
-            store_friend_to_file, FriendAddress, RelayAddress};
+use app::{public_key_to_string,
+            store_friend_to_file, FriendAddress, RelayAddress, PublicKey};
 use app::report::{NodeReport, 
     FriendReport, ChannelStatusReport,
-    FriendStatusReport};
+    FriendStatusReport, McBalanceReport, RequestsStatusReport};
+
+use crate::output::{OutputFormat, OutputError, print_output};
+use crate::profile::Profile;
+use crate::encoding::bytes_to_string;
+use crate::node::{Node, NodeError, AppError, NodeReportSource};
 
 
 #[derive(Debug)]
 pub enum InfoError {
-    GetReportError,
+    GetReportError(NodeError),
+    /// The node did not respond in time
+    Timeout(time::Duration),
     BalanceOverflow,
-    OutputFileAlreadyExists,
-    StoreNodeToFileError,
+    OutputFileAlreadyExists(PathBuf),
+    StoreNodeToFileError(PathBuf, AppError),
+    OutputError(OutputError),
+    ApplyMutationError(AppError),
+    ReportMutationsClosed,
+    FriendNameNotFound(String),
+    NoLastIncomingMoveToken(String),
+    SerializeLastFriendTokenError(toml::ser::Error),
+    StoreLastFriendTokenError(io::Error),
+}
+
+impl fmt::Display for InfoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InfoError::GetReportError(e) => write!(f, "failed to obtain a report from the node: {}", e),
+            InfoError::Timeout(timeout) => 
+                write!(f, "the node did not respond within {} seconds", timeout.as_secs()),
+            InfoError::BalanceOverflow => write!(f, "overflow when calculating balances"),
+            InfoError::OutputFileAlreadyExists(path) => 
+                write!(f, "output file already exists: {}", path.display()),
+            InfoError::StoreNodeToFileError(path, e) => 
+                write!(f, "failed to store node ticket to {}: {}", path.display(), e),
+            InfoError::OutputError(e) => write!(f, "{}", e),
+            InfoError::ApplyMutationError(e) => 
+                write!(f, "failed to apply a report mutation from the node: {}", e),
+            InfoError::ReportMutationsClosed => write!(f, "the node closed the connection"),
+            InfoError::FriendNameNotFound(name) => write!(f, "no friend named {:?}", name),
+            InfoError::NoLastIncomingMoveToken(name) => 
+                write!(f, "no move token was received from {:?} yet", name),
+            InfoError::SerializeLastFriendTokenError(e) => 
+                write!(f, "failed to serialize last friend token: {}", e),
+            InfoError::StoreLastFriendTokenError(e) => 
+                write!(f, "failed to store last friend token: {}", e),
+        }
+    }
+}
+
+impl Error for InfoError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            InfoError::GetReportError(e) => Some(e),
+            InfoError::StoreNodeToFileError(_, e) => Some(e),
+            InfoError::ApplyMutationError(e) => Some(e),
+            InfoError::OutputError(e) => Some(e),
+            InfoError::SerializeLastFriendTokenError(e) => Some(e),
+            InfoError::StoreLastFriendTokenError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Obtaining a report failed. A timeout is reported on its own.
+fn get_report_error(e: NodeError) -> InfoError {
+    match e {
+        NodeError::Timeout(timeout) => InfoError::Timeout(timeout),
+        e => InfoError::GetReportError(e),
+    }
+}
+
+impl From<OutputError> for InfoError {
+    fn from(e: OutputError) -> Self {
+        InfoError::OutputError(e)
+    }
 }
 
 /// Get a most recently known node report:
-async fn get_report(app_report: &mut AppReport) -> Result<NodeReport, InfoError> {
+async fn get_report<'a, R: NodeReportSource>(app_report: &'a mut R) -> Result<NodeReport, InfoError> {
 
     let (node_report, incoming_mutations) = await!(app_report.incoming_reports())
-        .map_err(|_| InfoError::GetReportError)?;
+        .map_err(get_report_error)?;
     // We currently don't need live updates about report mutations:
     drop(incoming_mutations);
 
     Ok(node_report)
 }
 
-pub async fn info_relays(mut app_report: AppReport) -> Result<(), InfoError> {
+/// Call `on_report` with the current report, and again after every batch of mutations 
+/// was applied to it. Only returns once the node closes the connection.
+async fn watch_report<'a, R, F>(app_report: &'a mut R, mut on_report: F) -> Result<(), InfoError>
+where
+    R: NodeReportSource,
+    F: FnMut(&NodeReport),
+{
+    let (mut node_report, mut incoming_mutations) = await!(app_report.incoming_reports())
+        .map_err(get_report_error)?;
+
+    on_report(&node_report);
+
+    while let Some(mutations) = await!(incoming_mutations.next()) {
+        for mutation in &mutations {
+            node_report.mutate(mutation)
+                .map_err(|e| InfoError::ApplyMutationError(AppError::new(e)))?;
+        }
+        on_report(&node_report);
+    }
+
+    // The node closed the connection:
+    Err(InfoError::ReportMutationsClosed)
+}
+
+#[derive(Debug, Serialize)]
+pub struct RelayInfo {
+    pub name: String,
+    pub public_key: String,
+    pub address: String,
+}
+
+/// Relays configured on the node
+pub fn relays_info(report: &NodeReport) -> Vec<RelayInfo> {
+    report.funder_report.relays
+        .iter()
+        .map(|named_relay_address| RelayInfo {
+            name: named_relay_address.name.clone(),
+            public_key: public_key_to_string(&named_relay_address.public_key),
+            address: named_relay_address.address.to_string(),
+        })
+        .collect()
+}
+
+pub async fn info_relays<R: NodeReportSource>(mut app_report: R, format: OutputFormat) -> Result<(), InfoError> {
 
     let report = await!(get_report(&mut app_report))?;
+    let relays_info = relays_info(&report);
 
     let mut table = Table::new();
     // Add title:
@@ -39,45 +162,254 @@ pub async fn info_relays(mut app_report: AppReport) -> Result<(), InfoError> {
                        "public key", 
                        "address"]);
 
-    for named_relay_address in &report.funder_report.relays {
-        let pk_string = public_key_to_string(&named_relay_address.public_key);
-        table.add_row(row![named_relay_address.name, 
-                           pk_string, 
-                           named_relay_address.address]);
+    for relay_info in &relays_info {
+        table.add_row(row![relay_info.name, 
+                           relay_info.public_key, 
+                           relay_info.address]);
     }
-    table.printstd();
+
+    print_output(format, "relays", &table, &relays_info, &relays_info)?;
     Ok(())
 }
 
-pub async fn info_index(mut app_report: AppReport) -> Result<(), InfoError> {
-    let report = await!(get_report(&mut app_report))?;
+#[derive(Debug, Serialize)]
+pub struct IndexServerInfo {
+    pub name: String,
+    pub public_key: String,
+    pub address: String,
+    pub connected: bool,
+}
 
+/// Index servers configured on the node
+pub fn index_servers_info(report: &NodeReport) -> Vec<IndexServerInfo> {
+    let opt_connected_server = &report.index_client_report.opt_connected_server;
+    report.index_client_report.index_servers
+        .iter()
+        .map(|named_index_server_address| IndexServerInfo {
+            name: named_index_server_address.name.clone(),
+            public_key: public_key_to_string(&named_index_server_address.public_key),
+            address: named_index_server_address.address.to_string(),
+            connected: opt_connected_server.as_ref() == Some(&named_index_server_address.public_key),
+        })
+        .collect()
+}
+
+/// The node's index client: Its connection state and its configured index servers
+#[derive(Debug, Serialize)]
+pub struct IndexInfo {
+    pub state: IndexClientState,
+    /// Public key of the connected index server
+    pub connected_server: Option<String>,
+    pub index_servers: Vec<IndexServerInfo>,
+}
+
+/// Connection state of the node's index client.
+///
+/// The report does not tell about connection attempts. The index client keeps trying to 
+/// connect to its configured index servers, so it is considered connecting 
+/// whenever index servers are configured but none is connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexClientState {
+    Connecting,
+    Connected,
+    /// No index servers are configured
+    Disconnected,
+}
+
+impl fmt::Display for IndexClientState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IndexClientState::Connecting => write!(f, "connecting"),
+            IndexClientState::Connected => write!(f, "connected"),
+            IndexClientState::Disconnected => write!(f, "disconnected"),
+        }
+    }
+}
+
+pub fn index_client_state(report: &NodeReport) -> IndexClientState {
+    let index_client_report = &report.index_client_report;
+    if index_client_report.opt_connected_server.is_some() {
+        IndexClientState::Connected
+    } else if !index_client_report.index_servers.is_empty() {
+        IndexClientState::Connecting
+    } else {
+        IndexClientState::Disconnected
+    }
+}
+
+pub fn index_info(report: &NodeReport) -> IndexInfo {
+    IndexInfo {
+        state: index_client_state(report),
+        connected_server: report.index_client_report.opt_connected_server
+            .as_ref()
+            .map(public_key_to_string),
+        index_servers: index_servers_info(report),
+    }
+}
+
+/// Name of an index server, or its public key if the server is not configured
+fn index_server_name(report: &NodeReport, index_public_key: &PublicKey) -> String {
+    report.index_client_report.index_servers
+        .iter()
+        .find(|named_index_server_address| 
+              &named_index_server_address.public_key == index_public_key)
+        .map(|named_index_server_address| named_index_server_address.name.clone())
+        .unwrap_or_else(|| public_key_to_string(index_public_key))
+}
+
+/// A user friendly description of the index client's connection state
+fn index_client_state_str(report: &NodeReport) -> String {
+    match &report.index_client_report.opt_connected_server {
+        Some(index_public_key) => format!("connected to {}", index_server_name(report, index_public_key)),
+        None => index_client_state(report).to_string(),
+    }
+}
+
+fn index_servers_table(index_servers_info: &[IndexServerInfo]) -> Table {
     let mut table = Table::new();
     // Add title:
     table.add_row(row!["index server name",
                        "public key", 
                        "address"]);
 
-
-    let opt_connected_server = &report.index_client_report.opt_connected_server;
-    for named_index_server_address in &report.index_client_report.index_servers {
-
+    for index_server_info in index_servers_info {
         // The currently used index will have (*) next to his name:
-        let name = if opt_connected_server.as_ref() == Some(&named_index_server_address.public_key) {
-            named_index_server_address.name.clone() + " (*)"
+        let name = if index_server_info.connected {
+            index_server_info.name.clone() + " (*)"
         } else {
-            named_index_server_address.name.clone()
+            index_server_info.name.clone()
         };
 
-        let pk_string = public_key_to_string(&named_index_server_address.public_key);
         table.add_row(row![name,
-                           pk_string, 
-                           named_index_server_address.address]);
+                           index_server_info.public_key, 
+                           index_server_info.address]);
     }
-    table.printstd();
+    table
+}
+
+/// Show the index servers and the state of the index client.
+/// If `watch` is set, keep showing the state as it changes.
+pub async fn info_index<R: NodeReportSource>(mut app_report: R, 
+                                             format: OutputFormat,
+                                             watch: bool) -> Result<(), InfoError> {
+    if watch {
+        return await!(info_index_watch(app_report));
+    }
+
+    let report = await!(get_report(&mut app_report))?;
+    let index_info = index_info(&report);
+    let table = index_servers_table(&index_info.index_servers);
+
+    // The connection state is not part of the table, to keep the CSV output a plain table:
+    if format == OutputFormat::Table {
+        println!("Index client: {}", index_client_state_str(&report));
+    }
+    print_output(format, "index", &table, &index_info, &index_info.index_servers)?;
     Ok(())
 }
 
+/// Maximum amount of index server switches displayed by `info index --watch`
+const MAX_INDEX_SWITCHES: usize = 16;
+
+/// A change of the index client's connected server
+struct IndexSwitch {
+    time: DateTime<Utc>,
+    /// Description of the new state
+    state_str: String,
+}
+
+/// Connection history of the index client, as observed while watching the report
+struct IndexHistory {
+    /// Time the current state was first observed
+    state_since: DateTime<Utc>,
+    /// Was the current state already present when we started watching?
+    since_start: bool,
+    /// Most recent switches, oldest first
+    switches: VecDeque<IndexSwitch>,
+}
+
+impl IndexHistory {
+    fn new() -> Self {
+        IndexHistory {
+            state_since: Utc::now(),
+            since_start: true,
+            switches: VecDeque::new(),
+        }
+    }
+
+    fn add_switch(&mut self, report: &NodeReport) {
+        let now = Utc::now();
+        self.state_since = now;
+        self.since_start = false;
+        if self.switches.len() >= MAX_INDEX_SWITCHES {
+            self.switches.pop_front();
+        }
+        self.switches.push_back(IndexSwitch {
+            time: now,
+            state_str: index_client_state_str(report),
+        });
+    }
+}
+
+/// A human readable duration, for example: "1h 5m 3s"
+fn duration_str(duration: Duration) -> String {
+    let secs = duration.num_seconds().max(0);
+    let (hours, mins, secs) = (secs / 3600, (secs / 60) % 60, secs % 60);
+    if hours > 0 {
+        format!("{}h {}m {}s", hours, mins, secs)
+    } else if mins > 0 {
+        format!("{}m {}s", mins, secs)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+fn render_index_watch(report: &NodeReport, index_history: &IndexHistory) {
+    clear_screen();
+
+    // If the state was present before we started watching, we only know a lower bound:
+    let at_least = if index_history.since_start { "at least " } else { "" };
+    println!("Index client: {} (for {}{})", 
+             index_client_state_str(report),
+             at_least,
+             duration_str(Utc::now() - index_history.state_since));
+    println!();
+
+    index_servers_table(&index_servers_info(report)).printstd();
+    println!();
+
+    if index_history.switches.is_empty() {
+        println!("No switches between index servers observed");
+        return;
+    }
+
+    let mut table = Table::new();
+    // Add title:
+    table.add_row(row!["time", "index client"]);
+    for index_switch in &index_history.switches {
+        table.add_row(row![index_switch.time.to_rfc3339(), index_switch.state_str]);
+    }
+    table.printstd();
+}
+
+/// Continuously display the index client's connection state,
+/// keeping track of switches between index servers.
+pub async fn info_index_watch<R: NodeReportSource>(mut app_report: R) -> Result<(), InfoError> {
+    let mut index_history = IndexHistory::new();
+    let mut opt_prev_state = None;
+
+    await!(watch_report(&mut app_report, |node_report| {
+        let state = (index_client_state(node_report), 
+                     node_report.index_client_report.opt_connected_server.clone());
+        if opt_prev_state.as_ref().map_or(false, |prev_state| prev_state != &state) {
+            index_history.add_switch(node_report);
+        }
+        opt_prev_state = Some(state);
+        render_index_watch(node_report, &index_history);
+    }))
+}
+
 /// A user friendly string explaining the current channel status
 fn friend_channel_status(friend_report: &FriendReport) -> String {
     let mut res = String::new();
@@ -103,7 +435,65 @@ fn friend_channel_status(friend_report: &FriendReport) -> String {
     res
 }
 
-pub async fn info_friends(mut app_report: AppReport) -> Result<(), InfoError> {
+/// Channel status, as presented in JSON output.
+/// Credit values are represented as strings, because they might not fit
+/// into the numeric types of JSON parsers.
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ChannelStatusInfo {
+    Consistent {
+        balance: String,
+    },
+    Inconsistent {
+        local_reset_terms_balance: String,
+        remote_reset_terms_balance: Option<String>,
+    },
+}
+
+impl ChannelStatusInfo {
+    fn from_report(channel_status: &ChannelStatusReport) -> Self {
+        match channel_status {
+            ChannelStatusReport::Consistent(tc_report) => 
+                ChannelStatusInfo::Consistent {
+                    balance: tc_report.balance.balance.to_string(),
+                },
+            ChannelStatusReport::Inconsistent(channel_inconsistent_report) => 
+                ChannelStatusInfo::Inconsistent {
+                    local_reset_terms_balance: channel_inconsistent_report
+                        .local_reset_terms_balance.to_string(),
+                    remote_reset_terms_balance: channel_inconsistent_report
+                        .opt_remote_reset_terms
+                        .as_ref()
+                        .map(|remote_reset_terms| remote_reset_terms.balance_for_reset.to_string()),
+                },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FriendInfo {
+    pub name: String,
+    pub public_key: String,
+    pub enabled: bool,
+    pub online: bool,
+    pub channel: ChannelStatusInfo,
+}
+
+/// Friends of the node, with their current status
+pub fn friends_info(report: &NodeReport) -> Vec<FriendInfo> {
+    report.funder_report.friends
+        .iter()
+        .map(|(friend_public_key, friend_report)| FriendInfo {
+            name: friend_report.name.clone(),
+            public_key: public_key_to_string(friend_public_key),
+            enabled: friend_report.status == FriendStatusReport::Enabled,
+            online: friend_report.liveness.is_online(),
+            channel: ChannelStatusInfo::from_report(&friend_report.channel_status),
+        })
+        .collect()
+}
+
+pub async fn info_friends<R: NodeReportSource>(mut app_report: R, format: OutputFormat) -> Result<(), InfoError> {
     let report = await!(get_report(&mut app_report))?;
 
     let mut table = Table::new();
@@ -113,7 +503,7 @@ pub async fn info_friends(mut app_report: AppReport) -> Result<(), InfoError> {
                        "liveness",
                        "channel status"]);
 
-    for (_friend_public_key, friend_report) in &report.funder_report.friends {
+    for friend_report in report.funder_report.friends.values() {
 
         // Is the friend enabled?
         let status_str = if friend_report.status == FriendStatusReport::Enabled {
@@ -134,18 +524,266 @@ pub async fn info_friends(mut app_report: AppReport) -> Result<(), InfoError> {
                            friend_channel_status(&friend_report)]);
     }
 
-    table.printstd();
+    let friends_info = friends_info(&report);
+
+    print_output(format, "friends", &table, &friends_info, &friends_info)?;
+    Ok(())
+}
+
+fn requests_status_str(requests_status: &RequestsStatusReport) -> &'static str {
+    match requests_status {
+        RequestsStatusReport::Open => "open",
+        RequestsStatusReport::Closed => "closed",
+    }
+}
+
+/// Reset terms of an inconsistent channel, as presented in JSON output
+#[derive(Debug, Serialize)]
+pub struct ResetTermsInfo {
+    pub local_reset_terms_balance: String,
+    pub remote_reset_terms_balance: Option<String>,
+    pub remote_reset_token: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FriendDetailInfo {
+    pub name: String,
+    pub public_key: String,
+    pub relays: Vec<RelayInfo>,
+    pub enabled: bool,
+    pub online: bool,
+    pub wanted_remote_max_debt: String,
+    pub wanted_local_requests_status: String,
+    /// Only known for consistent channels
+    pub balance: Option<FriendBalanceInfo>,
+    pub local_requests_status: Option<String>,
+    pub remote_requests_status: Option<String>,
+    /// Only present for inconsistent channels
+    pub reset_terms: Option<ResetTermsInfo>,
+}
+
+/// Detailed information about a single friend
+pub fn friend_detail_info(report: &NodeReport, friend_name: &str) -> Result<FriendDetailInfo, InfoError> {
+    let mut opt_friend_pk_report = None;
+    for (friend_public_key, friend_report) in &report.funder_report.friends {
+        if friend_report.name == friend_name {
+            opt_friend_pk_report = Some((friend_public_key, friend_report));
+        }
+    }
+
+    let (friend_public_key, friend_report) = opt_friend_pk_report
+        .ok_or_else(|| InfoError::FriendNameNotFound(friend_name.to_owned()))?;
+
+    let relays: Vec<RelayInfo> = friend_report.remote_relays
+        .iter()
+        .map(|relay_address| RelayInfo {
+            // Relays of friends are not named:
+            name: String::new(),
+            public_key: public_key_to_string(&relay_address.public_key),
+            address: relay_address.address.to_string(),
+        })
+        .collect();
+
+    let mut friend_detail_info = FriendDetailInfo {
+        name: friend_report.name.clone(),
+        public_key: public_key_to_string(friend_public_key),
+        relays,
+        enabled: friend_report.status == FriendStatusReport::Enabled,
+        online: friend_report.liveness.is_online(),
+        wanted_remote_max_debt: friend_report.wanted_remote_max_debt.to_string(),
+        wanted_local_requests_status: 
+            requests_status_str(&friend_report.wanted_local_requests_status).to_owned(),
+        balance: None,
+        local_requests_status: None,
+        remote_requests_status: None,
+        reset_terms: None,
+    };
+
+    match &friend_report.channel_status {
+        ChannelStatusReport::Consistent(tc_report) => {
+            let mc_balance = &tc_report.balance;
+            let (spendable, receivable) = channel_capacity(mc_balance)?;
+            friend_detail_info.balance = Some(FriendBalanceInfo {
+                name: friend_report.name.clone(),
+                consistent: true,
+                balance: mc_balance.balance.to_string(),
+                local_max_debt: Some(mc_balance.local_max_debt.to_string()),
+                remote_max_debt: Some(mc_balance.remote_max_debt.to_string()),
+                local_pending_debt: Some(mc_balance.local_pending_debt.to_string()),
+                remote_pending_debt: Some(mc_balance.remote_pending_debt.to_string()),
+                spendable: Some(spendable.to_string()),
+                receivable: Some(receivable.to_string()),
+            });
+            friend_detail_info.local_requests_status = 
+                Some(requests_status_str(&tc_report.requests_status.local).to_owned());
+            friend_detail_info.remote_requests_status = 
+                Some(requests_status_str(&tc_report.requests_status.remote).to_owned());
+        },
+        ChannelStatusReport::Inconsistent(channel_inconsistent_report) => {
+            let opt_remote_reset_terms = &channel_inconsistent_report.opt_remote_reset_terms;
+            friend_detail_info.reset_terms = Some(ResetTermsInfo {
+                local_reset_terms_balance: channel_inconsistent_report
+                    .local_reset_terms_balance.to_string(),
+                remote_reset_terms_balance: opt_remote_reset_terms
+                    .as_ref()
+                    .map(|remote_reset_terms| remote_reset_terms.balance_for_reset.to_string()),
+                remote_reset_token: opt_remote_reset_terms
+                    .as_ref()
+                    .map(|remote_reset_terms| bytes_to_string(&remote_reset_terms.reset_token[..])),
+            });
+        },
+    }
+
+    Ok(friend_detail_info)
+}
+
+/// Show detailed information about a single friend
+pub async fn info_friend<'a, R: NodeReportSource>(mut app_report: R,
+                             friend_name: &'a str,
+                             format: OutputFormat) -> Result<(), InfoError> {
+
+    let report = await!(get_report(&mut app_report))?;
+    let friend_detail_info = friend_detail_info(&report, friend_name)?;
+
+    let mut table = Table::new();
+    let unknown = || "-".to_owned();
+    table.add_row(row!["name", friend_detail_info.name]);
+    table.add_row(row!["public key", friend_detail_info.public_key]);
+    let relays_str = friend_detail_info.relays
+        .iter()
+        .map(|relay_info| format!("{} ({})", relay_info.address, relay_info.public_key))
+        .collect::<Vec<_>>()
+        .join("\n");
+    table.add_row(row!["relays", relays_str]);
+    table.add_row(row!["status", if friend_detail_info.enabled { "enabled" } else { "disabled" }]);
+    table.add_row(row!["liveness", if friend_detail_info.online { "online" } else { "offline" }]);
+    table.add_row(row!["wanted remote max debt", friend_detail_info.wanted_remote_max_debt]);
+    table.add_row(row!["wanted local requests", friend_detail_info.wanted_local_requests_status]);
+
+    if let Some(balance_info) = &friend_detail_info.balance {
+        table.add_row(row!["channel", "consistent"]);
+        table.add_row(row!["local requests", 
+                           friend_detail_info.local_requests_status.clone().unwrap_or_else(unknown)]);
+        table.add_row(row!["remote requests", 
+                           friend_detail_info.remote_requests_status.clone().unwrap_or_else(unknown)]);
+        table.add_row(row!["balance", balance_info.balance]);
+        table.add_row(row!["local max debt", balance_info.local_max_debt.clone().unwrap_or_else(unknown)]);
+        table.add_row(row!["remote max debt", balance_info.remote_max_debt.clone().unwrap_or_else(unknown)]);
+        table.add_row(row!["local pending debt", balance_info.local_pending_debt.clone().unwrap_or_else(unknown)]);
+        table.add_row(row!["remote pending debt", balance_info.remote_pending_debt.clone().unwrap_or_else(unknown)]);
+        table.add_row(row!["spendable", balance_info.spendable.clone().unwrap_or_else(unknown)]);
+        table.add_row(row!["receivable", balance_info.receivable.clone().unwrap_or_else(unknown)]);
+    }
+
+    if let Some(reset_terms_info) = &friend_detail_info.reset_terms {
+        table.add_row(row!["channel", "inconsistent"]);
+
+        // Show local and remote reset terms side by side:
+        let mut reset_table = Table::new();
+        reset_table.add_row(row!["", "local", "remote"]);
+        reset_table.add_row(row!["balance for reset", 
+                                 reset_terms_info.local_reset_terms_balance,
+                                 reset_terms_info.remote_reset_terms_balance.clone().unwrap_or_else(unknown)]);
+        reset_table.add_row(row!["reset token", 
+                                 "-",
+                                 reset_terms_info.remote_reset_token.clone().unwrap_or_else(unknown)]);
+        table.add_row(row!["reset terms", reset_table]);
+    }
+
+    print_output(format, "friend", &table, &friend_detail_info, 
+                 slice::from_ref(&friend_detail_info))?;
     Ok(())
 }
 
-pub async fn info_last_friend_token<'a>(_matches: &'a ArgMatches<'a>, 
-                                    _app_report: AppReport) -> Result<(), InfoError> {
-    // TODO: Should possibly export a file
-    unimplemented!();
-    /*
-    let idfile = matches.value_of("idfile").unwrap();
-    let output = matches.value_of("output").unwrap();
-    */
+/// Last incoming move token received from a friend, as stored in a file.
+///
+/// The file is in TOML format. Public keys are encoded the same way as in stctrl's
+/// other outputs, other binary values (hashes, nonces and signatures) are encoded 
+/// using url safe base64 without padding. Credit values are stored as decimal strings.
+///
+/// Example:
+/// ```text
+/// friend_name = "bob"
+/// prefix_hash = "..."
+/// local_public_key = "..."
+/// remote_public_key = "..."
+/// inconsistency_counter = 0
+/// move_token_counter = "13"
+/// balance = "-20"
+/// local_pending_debt = "0"
+/// remote_pending_debt = "5"
+/// rand_nonce = "..."
+/// new_token = "..."
+/// ```
+///
+/// `new_token` is the friend's signature over the rest of the fields, which allows
+/// to show this file to a third party as a proof of the mutual balance.
+#[derive(Debug, Serialize)]
+pub struct LastFriendTokenFile {
+    pub friend_name: String,
+    pub prefix_hash: String,
+    pub local_public_key: String,
+    pub remote_public_key: String,
+    pub inconsistency_counter: u64,
+    pub move_token_counter: String,
+    pub balance: String,
+    pub local_pending_debt: String,
+    pub remote_pending_debt: String,
+    pub rand_nonce: String,
+    pub new_token: String,
+}
+
+/// Get the last incoming move token received from a friend
+pub fn last_friend_token(report: &NodeReport, friend_name: &str) -> Result<LastFriendTokenFile, InfoError> {
+    let mut opt_friend_report = None;
+    for (_friend_public_key, friend_report) in &report.funder_report.friends {
+        if friend_report.name == friend_name {
+            opt_friend_report = Some(friend_report);
+        }
+    }
+
+    let friend_report = opt_friend_report
+        .ok_or_else(|| InfoError::FriendNameNotFound(friend_name.to_owned()))?;
+
+    let move_token = friend_report.opt_last_incoming_move_token
+        .as_ref()
+        .ok_or_else(|| InfoError::NoLastIncomingMoveToken(friend_name.to_owned()))?;
+
+    Ok(LastFriendTokenFile {
+        friend_name: friend_report.name.clone(),
+        prefix_hash: bytes_to_string(&move_token.prefix_hash[..]),
+        local_public_key: public_key_to_string(&move_token.local_public_key),
+        remote_public_key: public_key_to_string(&move_token.remote_public_key),
+        inconsistency_counter: move_token.inconsistency_counter,
+        move_token_counter: move_token.move_token_counter.to_string(),
+        balance: move_token.balance.to_string(),
+        local_pending_debt: move_token.local_pending_debt.to_string(),
+        remote_pending_debt: move_token.remote_pending_debt.to_string(),
+        rand_nonce: bytes_to_string(&move_token.rand_nonce[..]),
+        new_token: bytes_to_string(&move_token.new_token[..]),
+    })
+}
+
+/// Store the last incoming move token received from a friend into `output_path`,
+/// which must not exist.
+pub async fn info_last_friend_token<'a, R: NodeReportSource>(mut app_report: R,
+                                    friend_name: &'a str,
+                                    output_path: &'a Path) -> Result<LastFriendTokenFile, InfoError> {
+
+    if output_path.exists() {
+        return Err(InfoError::OutputFileAlreadyExists(output_path.to_path_buf()));
+    }
+
+    let report = await!(get_report(&mut app_report))?;
+    let last_friend_token_file = last_friend_token(&report, friend_name)?;
+
+    let data = toml::to_string(&last_friend_token_file)
+        .map_err(InfoError::SerializeLastFriendTokenError)?;
+
+    fs::write(output_path, data)
+        .map_err(InfoError::StoreLastFriendTokenError)?;
+
+    Ok(last_friend_token_file)
 }
 
 /// Get an approximate value for mutual balance with a friend.
@@ -159,60 +797,328 @@ fn friend_balance(friend_report: &FriendReport) -> i128 {
     }
 }
 
-pub async fn info_balance(mut app_report: AppReport) -> Result<(), InfoError> {
-    let report = await!(get_report(&mut app_report))?;
+/// Balance information of a single friend.
+/// Max debts and pending debts are only known for consistent channels.
+#[derive(Debug, Serialize)]
+pub struct FriendBalanceInfo {
+    pub name: String,
+    pub consistent: bool,
+    pub balance: String,
+    pub local_max_debt: Option<String>,
+    pub remote_max_debt: Option<String>,
+    /// Debt of pending requests we sent to the friend
+    pub local_pending_debt: Option<String>,
+    /// Debt of pending requests the friend sent to us
+    pub remote_pending_debt: Option<String>,
+    pub spendable: Option<String>,
+    pub receivable: Option<String>,
+}
 
-    let total_balance: i128 = 0;
-    for (_friend_public_key, friend_report) in &report.funder_report.friends {
-        total_balance.checked_add(friend_balance(&friend_report))
-            .ok_or(InfoError::BalanceOverflow)?;
+#[derive(Debug, Serialize)]
+pub struct BalanceInfo {
+    pub total_balance: String,
+    pub consistent_balance: String,
+    pub inconsistent_balance: String,
+    pub local_max_debt: String,
+    pub remote_max_debt: String,
+    pub spendable: String,
+    pub receivable: String,
+    pub friends: Vec<FriendBalanceInfo>,
+}
+
+/// Convert a credit amount to a signed value, checking for overflow
+fn to_signed(value: u128) -> Result<i128, InfoError> {
+    if value > i128::max_value() as u128 {
+        return Err(InfoError::BalanceOverflow);
     }
+    Ok(value as i128)
+}
+
+/// Calculate the amount of credits we can still send (spendable) 
+/// and receive (receivable) through a consistent channel.
+///
+/// The balance can go down to `-local_max_debt` and up to `remote_max_debt`. 
+/// Pending debts are already reserved for in-flight requests.
+fn channel_capacity(mc_balance: &McBalanceReport) -> Result<(i128, i128), InfoError> {
+    let local_max_debt = to_signed(mc_balance.local_max_debt)?;
+    let remote_max_debt = to_signed(mc_balance.remote_max_debt)?;
+    let local_pending_debt = to_signed(mc_balance.local_pending_debt)?;
+    let remote_pending_debt = to_signed(mc_balance.remote_pending_debt)?;
+
+    let spendable = mc_balance.balance
+        .checked_add(local_max_debt)
+        .and_then(|value| value.checked_sub(local_pending_debt))
+        .ok_or(InfoError::BalanceOverflow)?;
 
-    println!("Total balance: {}", total_balance);
+    let receivable = remote_max_debt
+        .checked_sub(mc_balance.balance)
+        .and_then(|value| value.checked_sub(remote_pending_debt))
+        .ok_or(InfoError::BalanceOverflow)?;
+
+    Ok((spendable.max(0), receivable.max(0)))
+}
+
+/// Add `value` to `acc`, checking for overflow
+fn checked_acc(acc: &mut i128, value: i128) -> Result<(), InfoError> {
+    *acc = acc.checked_add(value)
+        .ok_or(InfoError::BalanceOverflow)?;
     Ok(())
 }
 
-pub async fn info_export_ticket<'a>(matches: &'a ArgMatches<'a>, 
-                                mut app_report: AppReport) -> Result<(), InfoError> {
+/// Balance with every friend, and in total
+pub fn balance_info(report: &NodeReport) -> Result<BalanceInfo, InfoError> {
+    let mut total_balance: i128 = 0;
+    let mut consistent_balance: i128 = 0;
+    let mut inconsistent_balance: i128 = 0;
+    let mut local_max_debt: i128 = 0;
+    let mut remote_max_debt: i128 = 0;
+    let mut spendable: i128 = 0;
+    let mut receivable: i128 = 0;
+
+    let mut friends_info = Vec::new();
+    for (_friend_public_key, friend_report) in &report.funder_report.friends {
+        let balance = friend_balance(&friend_report);
+        checked_acc(&mut total_balance, balance)?;
+
+        let friend_balance_info = match &friend_report.channel_status {
+            ChannelStatusReport::Consistent(tc_report) => {
+                let mc_balance = &tc_report.balance;
+                let (friend_spendable, friend_receivable) = channel_capacity(mc_balance)?;
+
+                checked_acc(&mut consistent_balance, balance)?;
+                checked_acc(&mut local_max_debt, to_signed(mc_balance.local_max_debt)?)?;
+                checked_acc(&mut remote_max_debt, to_signed(mc_balance.remote_max_debt)?)?;
+                checked_acc(&mut spendable, friend_spendable)?;
+                checked_acc(&mut receivable, friend_receivable)?;
 
-    let output_file = matches.value_of("output_file").unwrap();
-    let output_pathbuf = PathBuf::from(output_file);
+                FriendBalanceInfo {
+                    name: friend_report.name.clone(),
+                    consistent: true,
+                    balance: balance.to_string(),
+                    local_max_debt: Some(mc_balance.local_max_debt.to_string()),
+                    remote_max_debt: Some(mc_balance.remote_max_debt.to_string()),
+                    local_pending_debt: Some(mc_balance.local_pending_debt.to_string()),
+                    remote_pending_debt: Some(mc_balance.remote_pending_debt.to_string()),
+                    spendable: Some(friend_spendable.to_string()),
+                    receivable: Some(friend_receivable.to_string()),
+                }
+            },
+            ChannelStatusReport::Inconsistent(_) => {
+                checked_acc(&mut inconsistent_balance, balance)?;
 
-    if output_pathbuf.exists() {
-        return Err(InfoError::OutputFileAlreadyExists);
+                FriendBalanceInfo {
+                    name: friend_report.name.clone(),
+                    consistent: false,
+                    balance: balance.to_string(),
+                    local_max_debt: None,
+                    remote_max_debt: None,
+                    local_pending_debt: None,
+                    remote_pending_debt: None,
+                    spendable: None,
+                    receivable: None,
+                }
+            },
+        };
+        friends_info.push(friend_balance_info);
     }
 
+    Ok(BalanceInfo {
+        total_balance: total_balance.to_string(),
+        consistent_balance: consistent_balance.to_string(),
+        inconsistent_balance: inconsistent_balance.to_string(),
+        local_max_debt: local_max_debt.to_string(),
+        remote_max_debt: remote_max_debt.to_string(),
+        spendable: spendable.to_string(),
+        receivable: receivable.to_string(),
+        friends: friends_info,
+    })
+}
+
+pub async fn info_balance<R: NodeReportSource>(mut app_report: R, format: OutputFormat) -> Result<(), InfoError> {
     let report = await!(get_report(&mut app_report))?;
+    let balance_info = balance_info(&report)?;
+
+    let mut table = Table::new();
+    // Add title:
+    table.add_row(row!["friend name",
+                       "channel",
+                       "balance",
+                       "local max debt",
+                       "remote max debt",
+                       "pending out",
+                       "pending in",
+                       "spendable",
+                       "receivable"]);
+
+    let unknown = || "-".to_owned();
+    for friend_balance_info in &balance_info.friends {
+        table.add_row(row![friend_balance_info.name,
+                           if friend_balance_info.consistent { "consistent" } else { "inconsistent" },
+                           friend_balance_info.balance,
+                           friend_balance_info.local_max_debt.clone().unwrap_or_else(unknown),
+                           friend_balance_info.remote_max_debt.clone().unwrap_or_else(unknown),
+                           friend_balance_info.local_pending_debt.clone().unwrap_or_else(unknown),
+                           friend_balance_info.remote_pending_debt.clone().unwrap_or_else(unknown),
+                           friend_balance_info.spendable.clone().unwrap_or_else(unknown),
+                           friend_balance_info.receivable.clone().unwrap_or_else(unknown)]);
+    }
+
+    table.add_row(row!["(total)",
+                       "",
+                       balance_info.total_balance,
+                       balance_info.local_max_debt,
+                       balance_info.remote_max_debt,
+                       "",
+                       "",
+                       balance_info.spendable,
+                       balance_info.receivable]);
+
+    // The totals are left out of the CSV output, to keep one row per friend:
+    print_output(format, "balance", &table, &balance_info, &balance_info.friends)?;
+
+    if format == OutputFormat::Table {
+        println!("Total balance: {} (consistent: {}, inconsistent: {})", 
+                 balance_info.total_balance, balance_info.consistent_balance, 
+                 balance_info.inconsistent_balance);
+        println!("Spendable: {}", balance_info.spendable);
+        println!("Receivable: {}", balance_info.receivable);
+    }
+    Ok(())
+}
+
+/// Clear the terminal and move the cursor to the top left corner
+fn clear_screen() {
+    print!("\x1b[2J\x1b[H");
+}
+
+/// Render a summary of the current node state.
+/// Used by `info watch` to show live updates.
+fn render_watch(report: &NodeReport) {
+    clear_screen();
+
+    println!("Index client: {}", index_client_state_str(report));
+    println!();
+
+    let mut table = Table::new();
+    // Add title:
+    table.add_row(row!["friend name",
+                       "status",
+                       "liveness",
+                       "balance"]);
+
+    for (_friend_public_key, friend_report) in &report.funder_report.friends {
+        let status_str = if friend_report.status == FriendStatusReport::Enabled {
+            "enabled"
+        } else {
+            "disabled"
+        };
+
+        let liveness_str = if friend_report.liveness.is_online() {
+            "online"
+        } else {
+            "offline"
+        };
+
+        // Inconsistent channels are marked with (!)
+        let balance_str = match &friend_report.channel_status {
+            ChannelStatusReport::Consistent(_) => friend_balance(friend_report).to_string(),
+            ChannelStatusReport::Inconsistent(_) => format!("{} (!)", friend_balance(friend_report)),
+        };
+
+        table.add_row(row![friend_report.name,
+                           status_str,
+                           liveness_str,
+                           balance_str]);
+    }
+    table.printstd();
+}
+
+/// Continuously display the state of the node, 
+/// updating the display on every report mutation.
+pub async fn info_watch<R: NodeReportSource>(mut app_report: R) -> Result<(), InfoError> {
+    await!(watch_report(&mut app_report, render_watch))
+}
+
+/// The ticket of our node: our public key and our relays
+pub fn node_ticket(report: &NodeReport) -> FriendAddress {
     let relays: Vec<RelayAddress> = report
         .funder_report
         .relays
-        .into_iter()
+        .iter()
+        .cloned()
         .map(|relay| relay.into())
         .collect();
 
-    let node_address = FriendAddress {
+    FriendAddress {
         public_key: report.funder_report.local_public_key.clone(),
         relays,
-    };
+    }
+}
+
+/// Store the ticket of our node into `output_path`, which must not exist
+pub async fn info_export_ticket<'a, R: NodeReportSource>(mut app_report: R,
+                                output_path: &'a Path) -> Result<FriendAddress, InfoError> {
+
+    if output_path.exists() {
+        return Err(InfoError::OutputFileAlreadyExists(output_path.to_path_buf()));
+    }
+
+    let report = await!(get_report(&mut app_report))?;
+    let node_address = node_ticket(&report);
+
+    store_friend_to_file(&node_address, output_path)
+        .map_err(|e| InfoError::StoreNodeToFileError(output_path.to_path_buf(), AppError::new(e)))?;
 
-    store_friend_to_file(&node_address, &output_pathbuf)
-        .map_err(|_| InfoError::StoreNodeToFileError)?;
+    Ok(node_address)
+}
 
+/// Dry run of a command that writes `what` into `output_path`: 
+/// Check that the file can be created, and show where it would be written.
+fn dry_run_output(output_path: &Path, what: &str) -> Result<(), InfoError> {
+    if output_path.exists() {
+        return Err(InfoError::OutputFileAlreadyExists(output_path.to_path_buf()));
+    }
+    println!("Dry run. Would write the {} to {}", what, output_path.display());
     Ok(())
 }
 
-pub async fn info<'a>(matches: &'a ArgMatches<'a>, 
-                      mut node_connection: NodeConnection) -> Result<(), InfoError> {
+pub async fn info<'a, N: Node>(matches: &'a ArgMatches<'a>, 
+                      profile: &'a Profile,
+                      node_connection: &'a mut N) -> Result<(), InfoError> {
 
-    let app_report = node_connection.report().clone();
+    let mut app_report = node_connection.report().clone();
+    let format = OutputFormat::from_matches(matches, profile);
 
     match matches.subcommand() {
-        ("relays", Some(_matches)) => await!(info_relays(app_report))?,
-        ("index", Some(_matches)) => await!(info_index(app_report))?,
-        ("friends", Some(_matches)) => await!(info_friends(app_report))?,
-        ("last-friend-token", Some(matches)) => await!(info_last_friend_token(matches, app_report))?,
-        ("balance", Some(_matches)) => await!(info_balance(app_report))?,
-        ("export-ticket", Some(matches)) => await!(info_export_ticket(matches, app_report))?,
+        ("relays", Some(_matches)) => await!(info_relays(app_report, format))?,
+        ("index", Some(matches)) => 
+            await!(info_index(app_report, format, matches.is_present("watch")))?,
+        ("friends", Some(_matches)) => await!(info_friends(app_report, format))?,
+        ("friend", Some(matches)) => {
+            let friend_name = matches.value_of("friend_name").unwrap();
+            await!(info_friend(app_report, friend_name, format))?;
+        },
+        ("last-friend-token", Some(matches)) => {
+            let friend_name = matches.value_of("friend_name").unwrap();
+            let output_pathbuf = PathBuf::from(matches.value_of("output_file").unwrap());
+            if matches.is_present("dry_run") {
+                let report = await!(get_report(&mut app_report))?;
+                last_friend_token(&report, friend_name)?;
+                dry_run_output(&output_pathbuf, "last friend token")?;
+            } else {
+                await!(info_last_friend_token(app_report, friend_name, &output_pathbuf))?;
+            }
+        },
+        ("balance", Some(_matches)) => await!(info_balance(app_report, format))?,
+        ("watch", Some(_matches)) => await!(info_watch(app_report))?,
+        ("export-ticket", Some(matches)) => {
+            let output_pathbuf = PathBuf::from(matches.value_of("output_file").unwrap());
+            if matches.is_present("dry_run") {
+                dry_run_output(&output_pathbuf, "node ticket")?;
+            } else {
+                await!(info_export_ticket(app_report, &output_pathbuf))?;
+            }
+        },
         _ => unreachable!(),
     }
 
@@ -0,0 +1,130 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use app::{PublicKey, public_key_to_string, string_to_public_key};
+use app::invoice::{InvoiceId, INVOICE_ID_LEN};
+
+use crate::encoding::{bytes_to_string, string_to_bytes};
+
+#[derive(Debug)]
+pub enum InvoiceFileError {
+    ReadError(io::Error),
+    WriteError(io::Error),
+    ParseError(toml::de::Error),
+    SerializeError(toml::ser::Error),
+    InvalidInvoiceId,
+    InvalidDestination,
+    InvalidAmount,
+}
+
+impl fmt::Display for InvoiceFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvoiceFileError::ReadError(e) => write!(f, "failed to read invoice file: {}", e),
+            InvoiceFileError::WriteError(e) => write!(f, "failed to write invoice file: {}", e),
+            InvoiceFileError::ParseError(e) => write!(f, "invalid invoice file: {}", e),
+            InvoiceFileError::SerializeError(e) => write!(f, "failed to serialize invoice: {}", e),
+            InvoiceFileError::InvalidInvoiceId => write!(f, "invalid invoice id"),
+            InvoiceFileError::InvalidDestination => write!(f, "invalid invoice destination"),
+            InvoiceFileError::InvalidAmount => write!(f, "invalid invoice amount"),
+        }
+    }
+}
+
+impl Error for InvoiceFileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            InvoiceFileError::ReadError(e) | InvoiceFileError::WriteError(e) => Some(e),
+            InvoiceFileError::ParseError(e) => Some(e),
+            InvoiceFileError::SerializeError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// A request for payment, created by the receiver of the funds 
+/// and sent to the payer.
+#[derive(Debug, Clone)]
+pub struct Invoice {
+    pub invoice_id: InvoiceId,
+    pub destination: PublicKey,
+    pub amount: u128,
+    pub memo: Option<String>,
+}
+
+/// Invoice, as stored in a file.
+///
+/// The file is in TOML format. The invoice id is encoded using url safe base64
+/// without padding, and the amount is stored as a decimal string.
+///
+/// Example:
+/// ```text
+/// invoice_id = "..."
+/// destination = "..."
+/// amount = "100"
+/// memo = "Order #1234"
+/// ```
+#[derive(Serialize, Deserialize)]
+struct InvoiceFile {
+    invoice_id: String,
+    destination: String,
+    amount: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memo: Option<String>,
+}
+
+/// Encode an invoice id into a string
+pub fn invoice_id_to_string(invoice_id: &InvoiceId) -> String {
+    bytes_to_string(&invoice_id[..])
+}
+
+/// Decode an invoice id from a string
+pub fn string_to_invoice_id(invoice_id_str: &str) -> Result<InvoiceId, InvoiceFileError> {
+    let invoice_id_vec = string_to_bytes(invoice_id_str)
+        .map_err(|_| InvoiceFileError::InvalidInvoiceId)?;
+
+    if invoice_id_vec.len() != INVOICE_ID_LEN {
+        return Err(InvoiceFileError::InvalidInvoiceId);
+    }
+
+    let mut invoice_id_array = [0u8; INVOICE_ID_LEN];
+    invoice_id_array.copy_from_slice(&invoice_id_vec);
+    Ok(InvoiceId::from(&invoice_id_array))
+}
+
+/// Load an invoice from a file
+pub fn load_invoice_from_file(path: &Path) -> Result<Invoice, InvoiceFileError> {
+    let data = fs::read_to_string(path)
+        .map_err(InvoiceFileError::ReadError)?;
+
+    let invoice_file: InvoiceFile = toml::from_str(&data)
+        .map_err(InvoiceFileError::ParseError)?;
+
+    Ok(Invoice {
+        invoice_id: string_to_invoice_id(&invoice_file.invoice_id)?,
+        destination: string_to_public_key(&invoice_file.destination)
+            .map_err(|_| InvoiceFileError::InvalidDestination)?,
+        amount: invoice_file.amount.parse::<u128>()
+            .map_err(|_| InvoiceFileError::InvalidAmount)?,
+        memo: invoice_file.memo,
+    })
+}
+
+/// Store an invoice into a file
+pub fn store_invoice_to_file(invoice: &Invoice, path: &Path) -> Result<(), InvoiceFileError> {
+    let invoice_file = InvoiceFile {
+        invoice_id: invoice_id_to_string(&invoice.invoice_id),
+        destination: public_key_to_string(&invoice.destination),
+        amount: invoice.amount.to_string(),
+        memo: invoice.memo.clone(),
+    };
+
+    let data = toml::to_string(&invoice_file)
+        .map_err(InvoiceFileError::SerializeError)?;
+
+    fs::write(path, data)
+        .map_err(InvoiceFileError::WriteError)
+}
@@ -0,0 +1,104 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum LedgerError {
+    ReadError(io::Error),
+    WriteError(io::Error),
+    /// Invalid entry at the given line
+    ParseError(usize, serde_json::Error),
+    SerializeError(serde_json::Error),
+}
+
+impl fmt::Display for LedgerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LedgerError::ReadError(e) => write!(f, "failed to read ledger: {}", e),
+            LedgerError::WriteError(e) => write!(f, "failed to write ledger: {}", e),
+            LedgerError::ParseError(line, e) => write!(f, "invalid ledger entry at line {}: {}", line, e),
+            LedgerError::SerializeError(e) => write!(f, "failed to serialize ledger entry: {}", e),
+        }
+    }
+}
+
+impl Error for LedgerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LedgerError::ReadError(e) | LedgerError::WriteError(e) => Some(e),
+            LedgerError::ParseError(_, e) | LedgerError::SerializeError(e) => Some(e),
+        }
+    }
+}
+
+/// Status of a payment that was completed
+pub const LEDGER_STATUS_SUCCESS: &str = "success";
+/// Status of a payment that failed before any credits were moved
+pub const LEDGER_STATUS_FAILURE: &str = "failure";
+
+/// A single payment attempt, as recorded in the ledger.
+///
+/// The ledger is an append only file, containing one JSON encoded entry per line.
+/// Values are encoded the same way as in receipt files: binary values using url safe base64
+/// without padding, and credit values as decimal strings.
+///
+/// Every part of a split payment is recorded as a separate entry.
+/// The parts share the same invoice id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub request_id: String,
+    pub timestamp: String,
+    pub destination: String,
+    pub route: Vec<String>,
+    pub amount: String,
+    pub fees: String,
+    pub invoice_id: String,
+    /// `LEDGER_STATUS_SUCCESS` or `LEDGER_STATUS_FAILURE`
+    pub status: String,
+}
+
+/// Append an entry to the ledger file. The file is created if it doesn't exist.
+pub fn append_to_ledger(ledger_entry: &LedgerEntry, path: &Path) -> Result<(), LedgerError> {
+    let mut line = serde_json::to_string(ledger_entry)
+        .map_err(LedgerError::SerializeError)?;
+    line.push('\n');
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(LedgerError::WriteError)?;
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(LedgerError::WriteError)?;
+
+    // A single write, to avoid interleaving with concurrent writers:
+    file.write_all(line.as_bytes())
+        .map_err(LedgerError::WriteError)
+}
+
+/// Load all the entries of the ledger file.
+/// A missing ledger file is treated as an empty ledger.
+pub fn load_ledger(path: &Path) -> Result<Vec<LedgerEntry>, LedgerError> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(LedgerError::ReadError(e)),
+    };
+
+    let mut ledger_entries = Vec::new();
+    for (i, line) in data.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        // Lines are numbered from 1:
+        let ledger_entry = serde_json::from_str(line)
+            .map_err(|e| LedgerError::ParseError(i + 1, e))?;
+        ledger_entries.push(ledger_entry);
+    }
+    Ok(ledger_entries)
+}
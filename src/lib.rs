@@ -10,8 +10,22 @@
 
 #[macro_use] extern crate prettytable;
 #[macro_use] extern crate log;
+#[macro_use] extern crate serde_derive;
 
 pub mod info;
 pub mod config;
 pub mod funds;
+pub mod output;
+pub mod invoice;
+pub mod receipt;
+pub mod encoding;
+pub mod data_dir;
+pub mod batch;
+pub mod ledger;
+pub mod address_book;
+pub mod profile;
+pub mod cli;
+pub mod shell;
+pub mod node;
+pub mod timeout;
 
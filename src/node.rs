@@ -0,0 +1,311 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use futures::{FutureExt, StreamExt};
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+
+use app::{NodeConnection, AppReport, AppConfig, AppRoutes, AppSendFunds,
+    NamedRelayAddress, NamedIndexServerAddress, RelayAddress, PublicKey, Receipt};
+use app::uid::Uid;
+use app::invoice::InvoiceId;
+use app::route::{FriendsRoute, RouteWithCapacity};
+use app::report::{NodeReport, NodeReportMutation, ResetTermsReport};
+
+// The traits in this module mirror the application interface of a node connection.
+// Commands are generic over these traits, which allows running them against a fake node
+// in tests.
+
+/// An error reported by the application interface of the node.
+/// These errors only implement `Debug`, so we keep their description.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppError(String);
+
+impl AppError {
+    pub fn new<E: fmt::Debug>(e: E) -> Self {
+        AppError(format!("{:?}", e))
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for AppError {}
+
+/// A failed node operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeError {
+    AppError(AppError),
+    /// The operation did not complete in time
+    Timeout(Duration),
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NodeError::AppError(e) => write!(f, "{}", e),
+            NodeError::Timeout(timeout) => 
+                write!(f, "timed out after {} seconds", timeout.as_secs()),
+        }
+    }
+}
+
+impl Error for NodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            NodeError::AppError(e) => Some(e),
+            NodeError::Timeout(_) => None,
+        }
+    }
+}
+
+/// Convert an error of the application interface
+fn app_error<E: fmt::Debug>(e: E) -> NodeError {
+    NodeError::AppError(AppError::new(e))
+}
+
+/// A live view of the node's report
+pub trait NodeReportSource: Clone + Send {
+    /// Get the current node report, and a stream of mutations to apply on top of it
+    fn incoming_reports(&mut self)
+        -> BoxFuture<'_, Result<(NodeReport, BoxStream<'static, Vec<NodeReportMutation>>), NodeError>>;
+}
+
+/// Configuration of the node
+pub trait NodeConfig: Clone + Send {
+    fn add_relay(&mut self, named_relay_address: NamedRelayAddress)
+        -> BoxFuture<'_, Result<(), NodeError>>;
+    fn remove_relay(&mut self, relay_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>>;
+    fn add_index_server(&mut self, named_index_server_address: NamedIndexServerAddress)
+        -> BoxFuture<'_, Result<(), NodeError>>;
+    fn remove_index_server(&mut self, index_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>>;
+    fn add_friend(&mut self,
+                  friend_public_key: PublicKey,
+                  relays: Vec<RelayAddress>,
+                  name: String,
+                  balance: i128) -> BoxFuture<'_, Result<(), NodeError>>;
+    fn set_friend_relays(&mut self, friend_public_key: PublicKey, relays: Vec<RelayAddress>)
+        -> BoxFuture<'_, Result<(), NodeError>>;
+    fn remove_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>>;
+    fn enable_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>>;
+    fn disable_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>>;
+    fn open_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>>;
+    fn close_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>>;
+    fn set_friend_remote_max_debt(&mut self, friend_public_key: PublicKey, remote_max_debt: u128)
+        -> BoxFuture<'_, Result<(), NodeError>>;
+    /// Accept the reset terms sent by the remote side
+    fn reset_friend_channel(&mut self, friend_public_key: PublicKey, remote_reset_terms: ResetTermsReport)
+        -> BoxFuture<'_, Result<(), NodeError>>;
+}
+
+/// Requesting routes from the index servers
+pub trait NodeRoutes: Clone + Send {
+    fn request_routes(&mut self,
+                      capacity: u128,
+                      source: PublicKey,
+                      destination: PublicKey,
+                      opt_exclude: Option<(PublicKey, PublicKey)>)
+        -> BoxFuture<'_, Result<Vec<RouteWithCapacity>, NodeError>>;
+}
+
+/// Sending funds
+pub trait NodeSendFunds: Clone + Send {
+    fn request_send_funds(&mut self,
+                          request_id: Uid,
+                          route: FriendsRoute,
+                          invoice_id: InvoiceId,
+                          dest_payment: u128) -> BoxFuture<'_, Result<Receipt, NodeError>>;
+    fn receipt_ack(&mut self, request_id: Uid, receipt: Receipt)
+        -> BoxFuture<'_, Result<(), NodeError>>;
+}
+
+/// A connection to a node. Only the report is always available,
+/// other capabilities depend on the permissions of the application.
+pub trait Node {
+    type Report: NodeReportSource;
+    type Config: NodeConfig;
+    type Routes: NodeRoutes;
+    type SendFunds: NodeSendFunds;
+
+    fn report(&mut self) -> &mut Self::Report;
+    fn config(&mut self) -> Option<&mut Self::Config>;
+    fn routes(&mut self) -> Option<&mut Self::Routes>;
+    fn send_funds(&mut self) -> Option<&mut Self::SendFunds>;
+}
+
+impl NodeReportSource for AppReport {
+    fn incoming_reports(&mut self)
+        -> BoxFuture<'_, Result<(NodeReport, BoxStream<'static, Vec<NodeReportMutation>>), NodeError>> {
+        async move {
+            await!(AppReport::incoming_reports(self))
+                .map(|(node_report, incoming_mutations)| (node_report, incoming_mutations.boxed()))
+                .map_err(app_error)
+        }.boxed()
+    }
+}
+
+impl NodeConfig for AppConfig {
+    fn add_relay(&mut self, named_relay_address: NamedRelayAddress)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        async move {
+            await!(AppConfig::add_relay(self, named_relay_address)).map_err(app_error)
+        }.boxed()
+    }
+
+    fn remove_relay(&mut self, relay_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        async move {
+            await!(AppConfig::remove_relay(self, relay_public_key)).map_err(app_error)
+        }.boxed()
+    }
+
+    fn add_index_server(&mut self, named_index_server_address: NamedIndexServerAddress)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        async move {
+            await!(AppConfig::add_index_server(self, named_index_server_address)).map_err(app_error)
+        }.boxed()
+    }
+
+    fn remove_index_server(&mut self, index_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        async move {
+            await!(AppConfig::remove_index_server(self, index_public_key)).map_err(app_error)
+        }.boxed()
+    }
+
+    fn add_friend(&mut self,
+                  friend_public_key: PublicKey,
+                  relays: Vec<RelayAddress>,
+                  name: String,
+                  balance: i128) -> BoxFuture<'_, Result<(), NodeError>> {
+        async move {
+            await!(AppConfig::add_friend(self, friend_public_key, relays, name, balance)).map_err(app_error)
+        }.boxed()
+    }
+
+    fn set_friend_relays(&mut self, friend_public_key: PublicKey, relays: Vec<RelayAddress>)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        async move {
+            await!(AppConfig::set_friend_relays(self, friend_public_key, relays)).map_err(app_error)
+        }.boxed()
+    }
+
+    fn remove_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        async move {
+            await!(AppConfig::remove_friend(self, friend_public_key)).map_err(app_error)
+        }.boxed()
+    }
+
+    fn enable_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        async move {
+            await!(AppConfig::enable_friend(self, friend_public_key)).map_err(app_error)
+        }.boxed()
+    }
+
+    fn disable_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        async move {
+            await!(AppConfig::disable_friend(self, friend_public_key)).map_err(app_error)
+        }.boxed()
+    }
+
+    fn open_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        async move {
+            await!(AppConfig::open_friend(self, friend_public_key)).map_err(app_error)
+        }.boxed()
+    }
+
+    fn close_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        async move {
+            await!(AppConfig::close_friend(self, friend_public_key)).map_err(app_error)
+        }.boxed()
+    }
+
+    fn set_friend_remote_max_debt(&mut self, friend_public_key: PublicKey, remote_max_debt: u128)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        async move {
+            await!(AppConfig::set_friend_remote_max_debt(self, friend_public_key, remote_max_debt))
+                .map_err(app_error)
+        }.boxed()
+    }
+
+    fn reset_friend_channel(&mut self, friend_public_key: PublicKey, remote_reset_terms: ResetTermsReport)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        async move {
+            await!(AppConfig::reset_friend_channel(self, friend_public_key, remote_reset_terms.reset_token))
+                .map_err(app_error)
+        }.boxed()
+    }
+}
+
+impl NodeRoutes for AppRoutes {
+    fn request_routes(&mut self,
+                      capacity: u128,
+                      source: PublicKey,
+                      destination: PublicKey,
+                      opt_exclude: Option<(PublicKey, PublicKey)>)
+        -> BoxFuture<'_, Result<Vec<RouteWithCapacity>, NodeError>> {
+        async move {
+            await!(AppRoutes::request_routes(self, capacity, source, destination, opt_exclude))
+                .map_err(app_error)
+        }.boxed()
+    }
+}
+
+impl NodeSendFunds for AppSendFunds {
+    fn request_send_funds(&mut self,
+                          request_id: Uid,
+                          route: FriendsRoute,
+                          invoice_id: InvoiceId,
+                          dest_payment: u128) -> BoxFuture<'_, Result<Receipt, NodeError>> {
+        async move {
+            await!(AppSendFunds::request_send_funds(self, request_id, route, invoice_id, dest_payment))
+                .map_err(app_error)
+        }.boxed()
+    }
+
+    fn receipt_ack(&mut self, request_id: Uid, receipt: Receipt)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        async move {
+            await!(AppSendFunds::receipt_ack(self, request_id, receipt)).map_err(app_error)
+        }.boxed()
+    }
+}
+
+impl Node for NodeConnection {
+    type Report = AppReport;
+    type Config = AppConfig;
+    type Routes = AppRoutes;
+    type SendFunds = AppSendFunds;
+
+    fn report(&mut self) -> &mut AppReport {
+        NodeConnection::report(self)
+    }
+
+    fn config(&mut self) -> Option<&mut AppConfig> {
+        NodeConnection::config(self)
+    }
+
+    fn routes(&mut self) -> Option<&mut AppRoutes> {
+        NodeConnection::routes(self)
+    }
+
+    fn send_funds(&mut self) -> Option<&mut AppSendFunds> {
+        NodeConnection::send_funds(self)
+    }
+}
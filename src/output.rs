@@ -0,0 +1,215 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use clap::ArgMatches;
+use prettytable::Table;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::profile::Profile;
+
+/// Version of the JSON output schema.
+/// Should be increased whenever a field is removed or changes its meaning.
+/// Adding new fields does not require a version bump.
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// All possible values for the `--format` argument
+pub const OUTPUT_FORMATS: &[&str] = &["table", "json", "csv"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+#[derive(Debug)]
+pub struct ParseOutputFormatError;
+
+impl FromStr for OutputFormat {
+    type Err = ParseOutputFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(ParseOutputFormatError),
+        }
+    }
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        OutputFormat::Table
+    }
+}
+
+impl OutputFormat {
+    /// Read the (global) `--format` argument, then the format of the profile. 
+    /// Defaults to a table.
+    pub fn from_matches<'a>(matches: &'a ArgMatches<'a>, profile: &'a Profile) -> OutputFormat {
+        if let Some(format_str) = matches.value_of("format") {
+            // Possible values are checked by clap:
+            return format_str.parse().unwrap();
+        }
+        if let Some(format_str) = &profile.format {
+            match format_str.parse() {
+                Ok(format) => return format,
+                Err(_) => warn!("Invalid profile format: {}. Using the default format", format_str),
+            }
+        }
+        OutputFormat::default()
+    }
+}
+
+#[derive(Debug)]
+pub enum OutputError {
+    /// Converting a CSV row into columns
+    SerializeCsvError(serde_json::Error),
+    WriteCsvError(csv::Error),
+    WriteJsonError(serde_json::Error),
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutputError::SerializeCsvError(e) => write!(f, "failed to serialize CSV output: {}", e),
+            OutputError::WriteCsvError(e) => write!(f, "failed to write CSV output: {}", e),
+            OutputError::WriteJsonError(e) => write!(f, "failed to write JSON output: {}", e),
+        }
+    }
+}
+
+impl Error for OutputError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            OutputError::SerializeCsvError(e) | OutputError::WriteJsonError(e) => Some(e),
+            OutputError::WriteCsvError(e) => Some(e),
+        }
+    }
+}
+
+/// The envelope of every JSON document we print
+#[derive(Serialize)]
+struct JsonOutput<'a, T> {
+    version: u32,
+    kind: &'a str,
+    data: &'a T,
+}
+
+/// A plain string for a JSON value that is not a container
+fn scalar_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Flatten a JSON value into (column, value) pairs.
+/// Nested fields are named by their path, joined with ".".
+/// Lists of plain values are kept in a single column, separated by spaces.
+fn flatten_json(path: &str, value: &Value, columns: &mut Vec<(String, String)>) {
+    let sub_path = |key: &str| {
+        if path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+
+    match value {
+        Value::Object(map) => {
+            for (key, sub_value) in map {
+                flatten_json(&sub_path(key), sub_value, columns);
+            }
+        },
+        Value::Array(items) if items.iter().all(|item| !item.is_object() && !item.is_array()) => {
+            let items_str: Vec<String> = items.iter().map(scalar_to_string).collect();
+            columns.push((path.to_owned(), items_str.join(" ")));
+        },
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                flatten_json(&sub_path(&index.to_string()), item, columns);
+            }
+        },
+        scalar => columns.push((path.to_owned(), scalar_to_string(scalar))),
+    }
+}
+
+/// Write `rows` to stdout as CSV, one line per row. 
+/// Columns are the union of the (flattened) fields of all rows.
+fn write_csv<R: Serialize>(rows: &[R]) -> Result<(), OutputError> {
+    let mut rows_columns = Vec::new();
+    for row in rows {
+        let value = serde_json::to_value(row)
+            .map_err(OutputError::SerializeCsvError)?;
+        let mut columns = Vec::new();
+        flatten_json("", &value, &mut columns);
+        rows_columns.push(columns);
+    }
+
+    // Rows might have different fields (For example, the fields of an enum variant):
+    let mut headers: Vec<String> = Vec::new();
+    for columns in &rows_columns {
+        for (header, _) in columns {
+            if !headers.contains(header) {
+                headers.push(header.clone());
+            }
+        }
+    }
+    if headers.is_empty() {
+        return Ok(());
+    }
+
+    let mut writer = csv::Writer::from_writer(io::stdout());
+    writer.write_record(&headers)
+        .map_err(OutputError::WriteCsvError)?;
+    for columns in &rows_columns {
+        let record = headers
+            .iter()
+            .map(|header| columns
+                 .iter()
+                 .find(|(column_header, _)| column_header == header)
+                 .map(|(_, column_value)| column_value.as_str())
+                 .unwrap_or(""));
+        writer.write_record(record)
+            .map_err(OutputError::WriteCsvError)?;
+    }
+    writer.flush()
+        .map_err(|e| OutputError::WriteCsvError(e.into()))?;
+    Ok(())
+}
+
+/// Print `table`, `data` or `csv_rows` to stdout, according to the requested output format.
+/// `kind` describes the type of `data`, to allow consumers of the JSON output
+/// to verify they are reading the document they expect.
+///
+/// The table is meant for humans only. CSV output is generated from `csv_rows`, 
+/// which should be the same structures as (or part of) `data`.
+pub fn print_output<T: Serialize, R: Serialize>(format: OutputFormat,
+                                                kind: &str,
+                                                table: &Table,
+                                                data: &T,
+                                                csv_rows: &[R]) -> Result<(), OutputError> {
+    match format {
+        OutputFormat::Table => {
+            table.printstd();
+        },
+        OutputFormat::Csv => write_csv(csv_rows)?,
+        OutputFormat::Json => {
+            let json_output = JsonOutput {
+                version: JSON_SCHEMA_VERSION,
+                kind,
+                data,
+            };
+            serde_json::to_writer_pretty(io::stdout(), &json_output)
+                .map_err(OutputError::WriteJsonError)?;
+            println!();
+        },
+    }
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const STCTRL_CONFIG_FILE: &str = "STCTRL_CONFIG_FILE";
+
+/// Default configuration file, relative to the user's configuration directory
+const DEFAULT_CONFIG_FILE: &str = "stctrl/config.toml";
+
+#[derive(Debug)]
+pub enum ProfileError {
+    ReadConfigFileError(PathBuf, io::Error),
+    ParseConfigFileError(PathBuf, toml::de::Error),
+    /// A profile was requested, but there is no configuration file
+    NoConfigFile(String),
+    ProfileNotFound(String),
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProfileError::ReadConfigFileError(path, e) => 
+                write!(f, "failed to read configuration file {}: {}", path.display(), e),
+            ProfileError::ParseConfigFileError(path, e) => 
+                write!(f, "invalid configuration file {}: {}", path.display(), e),
+            ProfileError::NoConfigFile(profile_name) => 
+                write!(f, "profile {:?} was requested, but there is no configuration file", profile_name),
+            ProfileError::ProfileNotFound(profile_name) => 
+                write!(f, "profile {:?} not found in the configuration file", profile_name),
+        }
+    }
+}
+
+impl Error for ProfileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ProfileError::ReadConfigFileError(_, e) => Some(e),
+            ProfileError::ParseConfigFileError(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Connection settings for a single node.
+/// Every setting is optional. Command line arguments take precedence over the settings
+/// of the profile. Environment variables only take precedence over the settings
+/// of the default profile, and not over a profile that was named explicitly.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Profile {
+    /// Client identity file path
+    pub idfile: Option<PathBuf>,
+    /// Node ticket file path
+    pub node_ticket: Option<PathBuf>,
+    /// Default output format
+    pub format: Option<String>,
+    /// Local data directory
+    pub data_dir: Option<PathBuf>,
+    /// Maximal amount of seconds to wait for a connection to the node
+    pub connect_timeout: Option<u64>,
+    /// Maximal amount of seconds to wait for a command to complete
+    pub timeout: Option<u64>,
+    /// Was the profile named explicitly (Using `--profile` or `STCTRL_PROFILE`),
+    /// rather than being the default profile of the configuration file?
+    #[serde(skip)]
+    pub explicit: bool,
+}
+
+impl Profile {
+    /// Choose between a setting of the profile and the value of an environment variable
+    pub fn setting_or_env<T>(&self, opt_setting: Option<T>, opt_env_value: Option<T>) -> Option<T> {
+        if self.explicit {
+            opt_setting.or(opt_env_value)
+        } else {
+            opt_env_value.or(opt_setting)
+        }
+    }
+}
+
+/// stctrl's configuration file.
+///
+/// The file is in TOML format. Relative paths are relative to the current directory.
+///
+/// Example:
+/// ```text
+/// default_profile = "home"
+///
+/// [profiles.home]
+/// idfile = "/home/user/offst/home.ident"
+/// node_ticket = "/home/user/offst/home_node.ticket"
+///
+/// [profiles.shop]
+/// idfile = "/home/user/offst/shop.ident"
+/// node_ticket = "/home/user/offst/shop_node.ticket"
+/// format = "json"
+/// data_dir = "/home/user/.stctrl-shop"
+/// connect_timeout = 10
+/// timeout = 60
+/// ```
+#[derive(Debug, Default, Deserialize)]
+struct ConfigFile {
+    /// The profile used when no profile was specified
+    default_profile: Option<String>,
+    #[serde(default)]
+    profiles: HashMap<String, Profile>,
+}
+
+/// Get the path of stctrl's configuration file
+///
+/// Taken from the `STCTRL_CONFIG_FILE` environment variable, and otherwise defaults to
+/// `$XDG_CONFIG_HOME/stctrl/config.toml` or `~/.config/stctrl/config.toml`.
+pub fn config_file_path() -> Option<PathBuf> {
+    if let Some(config_file) = env::var_os(STCTRL_CONFIG_FILE) {
+        return Some(PathBuf::from(config_file));
+    }
+    if let Some(config_home) = env::var_os("XDG_CONFIG_HOME") {
+        return Some(PathBuf::from(config_home).join(DEFAULT_CONFIG_FILE));
+    }
+    env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(".config").join(DEFAULT_CONFIG_FILE))
+}
+
+/// Load a configuration file. Returns None if the file does not exist.
+fn load_config_file(path: &Path) -> Result<Option<ConfigFile>, ProfileError> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(ProfileError::ReadConfigFileError(path.to_path_buf(), e)),
+    };
+
+    toml::from_str(&data)
+        .map(Some)
+        .map_err(|e| ProfileError::ParseConfigFileError(path.to_path_buf(), e))
+}
+
+/// Load a profile from the configuration file.
+///
+/// If `opt_profile_name` is None, the default profile of the configuration file is loaded, if any.
+/// It is an error to ask for a profile that does not exist.
+pub fn load_profile(opt_profile_name: Option<&str>) -> Result<Option<Profile>, ProfileError> {
+    let opt_config_file = match config_file_path() {
+        Some(config_file_pathbuf) => load_config_file(&config_file_pathbuf)?,
+        None => None,
+    };
+
+    let mut config_file = match opt_config_file {
+        Some(config_file) => config_file,
+        None => {
+            if let Some(profile_name) = opt_profile_name {
+                return Err(ProfileError::NoConfigFile(profile_name.to_owned()));
+            }
+            return Ok(None);
+        },
+    };
+
+    let profile_name = match opt_profile_name {
+        Some(profile_name) => profile_name.to_owned(),
+        None => match config_file.default_profile {
+            Some(default_profile) => default_profile,
+            None => return Ok(None),
+        },
+    };
+
+    match config_file.profiles.remove(&profile_name) {
+        Some(mut profile) => {
+            profile.explicit = opt_profile_name.is_some();
+            Ok(Some(profile))
+        },
+        None => Err(ProfileError::ProfileNotFound(profile_name)),
+    }
+}
@@ -0,0 +1,85 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use app::{PublicKey, public_key_to_string};
+
+#[derive(Debug)]
+pub enum ReceiptFileError {
+    SerializeError(toml::ser::Error),
+    WriteError(io::Error),
+}
+
+impl fmt::Display for ReceiptFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReceiptFileError::SerializeError(e) => write!(f, "failed to serialize receipt: {}", e),
+            ReceiptFileError::WriteError(e) => write!(f, "failed to write receipt file: {}", e),
+        }
+    }
+}
+
+impl Error for ReceiptFileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReceiptFileError::SerializeError(e) => Some(e),
+            ReceiptFileError::WriteError(e) => Some(e),
+        }
+    }
+}
+
+/// A receipt of a successful payment, together with information about the payment.
+///
+/// The file is in TOML format. Public keys are encoded the same way as in stctrl's
+/// other outputs, other binary values (request id, invoice id, hashes and signatures) 
+/// are encoded using url safe base64 without padding. 
+/// Credit values are stored as decimal strings.
+///
+/// Example:
+/// ```text
+/// request_id = "..."
+/// timestamp = "2019-03-20T12:00:00+00:00"
+/// destination = "..."
+/// route = ["...", "...", "..."]
+/// amount = "100"
+/// fees = "1"
+/// response_hash = "..."
+/// invoice_id = "..."
+/// dest_payment = "100"
+/// signature = "..."
+/// ```
+///
+/// `signature` is the destination's signature over the receipt, 
+/// which can be used as a proof that the payment took place.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReceiptFile {
+    pub request_id: String,
+    pub timestamp: String,
+    pub destination: String,
+    pub route: Vec<String>,
+    pub amount: String,
+    pub fees: String,
+    pub response_hash: String,
+    pub invoice_id: String,
+    pub dest_payment: String,
+    pub signature: String,
+}
+
+/// Encode a list of public keys (For example, a route) into strings
+pub fn public_keys_to_strings(public_keys: &[PublicKey]) -> Vec<String> {
+    public_keys
+        .iter()
+        .map(public_key_to_string)
+        .collect()
+}
+
+/// Store a receipt into a file
+pub fn store_receipt_to_file(receipt_file: &ReceiptFile, path: &Path) -> Result<(), ReceiptFileError> {
+    let data = toml::to_string(receipt_file)
+        .map_err(ReceiptFileError::SerializeError)?;
+
+    fs::write(path, data)
+        .map_err(ReceiptFileError::WriteError)
+}
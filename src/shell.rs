@@ -0,0 +1,425 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::iter;
+
+use futures::{poll, StreamExt};
+use futures::task::Poll;
+use termion::{clear, cursor};
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+
+use app::report::NodeReport;
+
+use crate::cli::{build_shell_app, CommandSpec, COMMANDS};
+use crate::info::info;
+use crate::config::config;
+use crate::funds::funds;
+use crate::node::{Node, NodeError, AppError, NodeReportSource};
+use crate::profile::Profile;
+
+const PROMPT: &str = "stctrl> ";
+
+/// Commands handled by the shell itself
+const BUILTIN_COMMANDS: &[&str] = &["help", "exit", "quit"];
+
+/// A command and its subcommands, as defined by the command line interface. 
+/// Used for tab completion.
+struct CommandTree {
+    name: String,
+    subcommands: Vec<CommandTree>,
+}
+
+impl CommandTree {
+    fn from_spec(command_spec: &CommandSpec) -> CommandTree {
+        CommandTree {
+            name: command_spec.name.to_owned(),
+            subcommands: command_spec.subcommands
+                .iter()
+                .map(CommandTree::from_spec)
+                .collect(),
+        }
+    }
+
+    fn subcommand(&self, name: &str) -> Option<&CommandTree> {
+        self.subcommands
+            .iter()
+            .find(|subcommand| subcommand.name == name)
+    }
+}
+
+/// Commands available in the shell
+fn shell_commands() -> CommandTree {
+    let mut commands = CommandTree {
+        name: "stctrl".to_owned(),
+        subcommands: COMMANDS
+            .iter()
+            // A shell can not be opened from within the shell:
+            .filter(|command_spec| command_spec.name != "shell")
+            .map(CommandTree::from_spec)
+            .collect(),
+    };
+    for builtin_command in BUILTIN_COMMANDS {
+        commands.subcommands.push(CommandTree {
+            name: (*builtin_command).to_owned(),
+            subcommands: Vec::new(),
+        });
+    }
+    commands
+}
+
+#[derive(Debug)]
+pub enum ShellError {
+    GetReportError(NodeError),
+    ApplyMutationError(AppError),
+    ReadLineError(io::Error),
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShellError::GetReportError(e) => write!(f, "failed to obtain a report from the node: {}", e),
+            ShellError::ApplyMutationError(e) => 
+                write!(f, "failed to apply a report mutation from the node: {}", e),
+            ShellError::ReadLineError(e) => write!(f, "failed to read input: {}", e),
+        }
+    }
+}
+
+impl Error for ShellError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ShellError::GetReportError(e) => Some(e),
+            ShellError::ApplyMutationError(e) => Some(e),
+            ShellError::ReadLineError(e) => Some(e),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct UnterminatedQuoteError;
+
+/// Split a command line into arguments.
+/// Arguments containing whitespace may be quoted using single or double quotes.
+fn split_line(line: &str) -> Result<Vec<String>, UnterminatedQuoteError> {
+    let mut args = Vec::new();
+    let mut cur_arg = String::new();
+    let mut in_arg = false;
+    let mut opt_quote = None;
+
+    for c in line.chars() {
+        match opt_quote {
+            Some(quote) => {
+                if c == quote {
+                    opt_quote = None;
+                } else {
+                    cur_arg.push(c);
+                }
+            },
+            None => {
+                if c == '"' || c == '\'' {
+                    opt_quote = Some(c);
+                    in_arg = true;
+                } else if c.is_whitespace() {
+                    if in_arg {
+                        args.push(cur_arg.split_off(0));
+                        in_arg = false;
+                    }
+                } else {
+                    cur_arg.push(c);
+                    in_arg = true;
+                }
+            },
+        }
+    }
+
+    if opt_quote.is_some() {
+        return Err(UnterminatedQuoteError);
+    }
+    if in_arg {
+        args.push(cur_arg);
+    }
+    Ok(args)
+}
+
+/// Names of friends, relays and index servers, used for tab completion
+fn report_names(node_report: &NodeReport) -> Vec<String> {
+    let mut names = Vec::new();
+    for friend_report in node_report.funder_report.friends.values() {
+        names.push(friend_report.name.clone());
+    }
+    for named_relay_address in &node_report.funder_report.relays {
+        names.push(named_relay_address.name.clone());
+    }
+    for named_index_server_address in &node_report.index_client_report.index_servers {
+        names.push(named_index_server_address.name.clone());
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Possible completions for the last word of `line`
+fn completion_candidates(line: &str, commands: &CommandTree, names: &[String]) -> Vec<String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    // The index of the word being completed:
+    let word_index = if line.ends_with(char::is_whitespace) || words.is_empty() {
+        words.len()
+    } else {
+        words.len() - 1
+    };
+
+    // Follow the subcommands typed so far:
+    let mut opt_command = Some(commands);
+    for word in &words[.. word_index] {
+        opt_command = opt_command.and_then(|command| command.subcommand(word));
+    }
+
+    match opt_command {
+        Some(command) if !command.subcommands.is_empty() => command.subcommands
+            .iter()
+            .map(|subcommand| subcommand.name.clone())
+            .collect(),
+        _ => names
+            .iter()
+            .map(|name| {
+                if name.contains(char::is_whitespace) {
+                    format!("\"{}\"", name)
+                } else {
+                    name.clone()
+                }
+            })
+            .collect(),
+    }
+}
+
+/// Complete the last word of `line`.
+/// Returns the completed line and the candidates, if the completion was ambiguous.
+fn complete(line: &str, commands: &CommandTree, names: &[String]) -> (String, Vec<String>) {
+    let word_start = line
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map(|(i, c)| i + c.len_utf8())
+        .unwrap_or(0);
+    let prefix = &line[word_start ..];
+
+    let candidates: Vec<String> = completion_candidates(line, commands, names)
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(prefix))
+        .collect();
+
+    match candidates.len() {
+        0 => (line.to_owned(), Vec::new()),
+        1 => (format!("{}{} ", &line[.. word_start], candidates[0]), Vec::new()),
+        _ => {
+            // Complete up to the longest common prefix:
+            let mut common = candidates[0].clone();
+            for candidate in &candidates[1 ..] {
+                while !candidate.starts_with(&common) {
+                    common.pop();
+                }
+            }
+            (format!("{}{}", &line[.. word_start], common), candidates)
+        },
+    }
+}
+
+/// Read a single line from a terminal, with line editing, history and tab completion.
+/// Returns None at the end of the input.
+fn read_line_tty(history: &[String], commands: &CommandTree, names: &[String]) -> io::Result<Option<String>> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout().into_raw_mode()?;
+
+    let mut buffer: Vec<char> = Vec::new();
+    let mut pos = 0;
+    // Position inside the history. history.len() means the line being edited:
+    let mut history_index = history.len();
+
+    write!(stdout, "{}", PROMPT)?;
+    stdout.flush()?;
+
+    for key in stdin.keys() {
+        match key? {
+            Key::Char('\n') => {
+                write!(stdout, "\r\n")?;
+                return Ok(Some(buffer.into_iter().collect()));
+            },
+            Key::Char('\t') => {
+                // Completion only applies at the end of the line:
+                let line: String = buffer.iter().collect();
+                let (completed_line, candidates) = complete(&line, commands, names);
+                if !candidates.is_empty() {
+                    write!(stdout, "\r\n{}\r\n", candidates.join("  "))?;
+                }
+                buffer = completed_line.chars().collect();
+                pos = buffer.len();
+            },
+            Key::Char(c) => {
+                buffer.insert(pos, c);
+                pos += 1;
+            },
+            Key::Backspace => {
+                if pos > 0 {
+                    pos -= 1;
+                    buffer.remove(pos);
+                }
+            },
+            Key::Delete => {
+                if pos < buffer.len() {
+                    buffer.remove(pos);
+                }
+            },
+            Key::Left => pos = pos.saturating_sub(1),
+            Key::Right => {
+                if pos < buffer.len() {
+                    pos += 1;
+                }
+            },
+            Key::Home | Key::Ctrl('a') => pos = 0,
+            Key::End | Key::Ctrl('e') => pos = buffer.len(),
+            Key::Up => {
+                if history_index > 0 {
+                    history_index -= 1;
+                    buffer = history[history_index].chars().collect();
+                    pos = buffer.len();
+                }
+            },
+            Key::Down => {
+                if history_index < history.len() {
+                    history_index += 1;
+                    buffer = match history.get(history_index) {
+                        Some(line) => line.chars().collect(),
+                        None => Vec::new(),
+                    };
+                    pos = buffer.len();
+                }
+            },
+            Key::Ctrl('u') => {
+                buffer.clear();
+                pos = 0;
+            },
+            Key::Ctrl('c') => {
+                // Abandon the current line:
+                write!(stdout, "^C\r\n")?;
+                return Ok(Some(String::new()));
+            },
+            Key::Ctrl('d') => {
+                if buffer.is_empty() {
+                    write!(stdout, "\r\n")?;
+                    return Ok(None);
+                }
+            },
+            _ => {},
+        }
+
+        // Redraw the line:
+        let line: String = buffer.iter().collect();
+        write!(stdout, "\r{}{}{}", clear::CurrentLine, PROMPT, line)?;
+        let num_left = buffer.len() - pos;
+        if num_left > 0 {
+            write!(stdout, "{}", cursor::Left(num_left as u16))?;
+        }
+        stdout.flush()?;
+    }
+    Ok(None)
+}
+
+/// Read a single line. Line editing is only available if stdin is a terminal.
+/// Returns None at the end of the input.
+fn read_line(history: &[String], commands: &CommandTree, names: &[String]) -> io::Result<Option<String>> {
+    if termion::is_tty(&io::stdin()) {
+        return read_line_tty(history, commands, names);
+    }
+
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line))
+}
+
+/// An interactive shell, running info, config and funds commands
+/// over a single connection to the node.
+pub async fn shell<'a, N: Node>(profile: &'a Profile, 
+                                node_connection: &'a mut N) -> Result<(), ShellError> {
+    let mut history: Vec<String> = Vec::new();
+    let commands = shell_commands();
+
+    // Names for tab completion are taken from a report that is kept up to date 
+    // for the whole session:
+    let mut app_report = node_connection.report().clone();
+    let (mut node_report, incoming_mutations) = await!(app_report.incoming_reports())
+        .map_err(ShellError::GetReportError)?;
+    let mut incoming_mutations = incoming_mutations.fuse();
+
+    loop {
+        // Apply the mutations received so far, without waiting for more:
+        while let Poll::Ready(Some(mutations)) = poll!(incoming_mutations.next()) {
+            for mutation in &mutations {
+                node_report.mutate(mutation)
+                    .map_err(|e| ShellError::ApplyMutationError(AppError::new(e)))?;
+            }
+        }
+        let names = report_names(&node_report);
+
+        let line = match read_line(&history, &commands, &names)
+            .map_err(ShellError::ReadLineError)? {
+            Some(line) => line,
+            None => break,
+        };
+
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if history.last().map(|last_line| last_line.as_str()) != Some(line) {
+            history.push(line.to_owned());
+        }
+
+        match line {
+            "exit" | "quit" => break,
+            "help" => {
+                let _ = build_shell_app().print_help();
+                println!();
+                continue;
+            },
+            _ => {},
+        }
+
+        let args = match split_line(line) {
+            Ok(args) => args,
+            Err(UnterminatedQuoteError) => {
+                println!("Unterminated quote");
+                continue;
+            },
+        };
+
+        // Connection arguments are rejected, as the shell is already connected:
+        let matches = match build_shell_app().get_matches_from_safe(iter::once("stctrl".to_owned()).chain(args)) {
+            Ok(matches) => matches,
+            Err(e) => {
+                // Also used for showing help messages:
+                println!("{}", e);
+                continue;
+            },
+        };
+
+        let res = match matches.subcommand() {
+            ("info", Some(matches)) => await!(info(matches, profile, node_connection))
+                .map_err(|e| e.to_string()),
+            ("config", Some(matches)) => await!(config(matches, node_connection))
+                .map_err(|e| e.to_string()),
+            ("funds", Some(matches)) => await!(funds(matches, profile, node_connection))
+                .map_err(|e| e.to_string()),
+            ("shell", Some(_)) => Err("Already inside a shell".to_owned()),
+            _ => unreachable!(),
+        };
+
+        if let Err(e) = res {
+            println!("Error: {}", e);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,359 @@
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::{select, Future, FutureExt};
+use futures::channel::oneshot;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+
+use app::{NamedRelayAddress, NamedIndexServerAddress, RelayAddress, PublicKey, Receipt};
+use app::uid::Uid;
+use app::invoice::InvoiceId;
+use app::route::{FriendsRoute, RouteWithCapacity};
+use app::report::{NodeReport, NodeReportMutation, ResetTermsReport};
+
+use crate::node::{Node, NodeError, NodeReportSource, NodeConfig, NodeRoutes, NodeSendFunds};
+
+#[derive(Debug)]
+pub struct TimeoutError;
+
+/// A request to be notified once `deadline` is reached
+struct TimerRequest {
+    deadline: Instant,
+    sender: oneshot::Sender<()>,
+}
+
+// Ordered so that the earliest deadline is at the top of a `BinaryHeap`:
+impl Ord for TimerRequest {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        other.deadline.cmp(&self.deadline)
+    }
+}
+
+impl PartialOrd for TimerRequest {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for TimerRequest {
+    fn eq(&self, other: &Self) -> bool {
+        self.deadline == other.deadline
+    }
+}
+
+impl Eq for TimerRequest {}
+
+/// Serve timer requests until all `Timer` handles are dropped and no request is pending.
+fn run_timer(request_receiver: mpsc::Receiver<TimerRequest>) {
+    let mut pending = BinaryHeap::new();
+    let mut connected = true;
+
+    loop {
+        let now = Instant::now();
+        while pending.peek().map_or(false, |request: &TimerRequest| request.deadline <= now) {
+            let request = pending.pop().unwrap();
+            // The receiver is gone if the guarded operation already completed:
+            let _ = request.sender.send(());
+        }
+
+        let opt_deadline = pending.peek().map(|request| request.deadline);
+        let recv_res = match (opt_deadline, connected) {
+            (None, false) => return,
+            (Some(deadline), false) => {
+                thread::sleep(deadline - now);
+                continue;
+            },
+            (None, true) => request_receiver.recv()
+                .map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+            (Some(deadline), true) => request_receiver.recv_timeout(deadline - now),
+        };
+
+        match recv_res {
+            Ok(request) => pending.push(request),
+            Err(mpsc::RecvTimeoutError::Timeout) => {},
+            Err(mpsc::RecvTimeoutError::Disconnected) => connected = false,
+        }
+    }
+}
+
+/// A handle to a single timer thread, shared by all the operations it limits.
+/// The timer does not depend on the executor running the operations.
+#[derive(Clone)]
+pub struct Timer {
+    request_sender: mpsc::Sender<TimerRequest>,
+}
+
+impl Timer {
+    /// Spawn the timer thread. It exits once all handles are dropped.
+    pub fn new() -> Self {
+        let (request_sender, request_receiver) = mpsc::channel();
+        thread::spawn(move || run_timer(request_receiver));
+        Timer {
+            request_sender,
+        }
+    }
+
+    /// Get a receiver that is notified after `duration`
+    fn sleep(&self, duration: Duration) -> oneshot::Receiver<()> {
+        let (sender, receiver) = oneshot::channel();
+        let request = TimerRequest {
+            deadline: Instant::now() + duration,
+            sender,
+        };
+        // If the timer thread is gone, the receiver is canceled, which counts as a timeout:
+        let _ = self.request_sender.send(request);
+        receiver
+    }
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wait for `fut` to complete, for at most `duration`.
+pub fn with_timeout<F>(timer: &Timer, fut: F, duration: Duration)
+    -> impl Future<Output=Result<F::Output, TimeoutError>>
+where
+    F: Future + Unpin,
+{
+    let timer_receiver = timer.sleep(duration);
+
+    async move {
+        let mut fut = fut.fuse();
+        let mut timer_receiver = timer_receiver.fuse();
+        select! {
+            output = fut => Ok(output),
+            _ = timer_receiver => Err(TimeoutError),
+        }
+    }
+}
+
+/// Fail `fut` if it does not complete within `opt_timeout`, and remember that a timeout occurred.
+fn guard<'a, T: Send + 'a>(timer: &Timer,
+                           opt_timeout: Option<Duration>,
+                           timed_out: Arc<AtomicBool>,
+                           fut: BoxFuture<'a, Result<T, NodeError>>) -> BoxFuture<'a, Result<T, NodeError>> {
+    let timeout = match opt_timeout {
+        Some(timeout) => timeout,
+        None => return fut,
+    };
+
+    // Register with the timer now, so that the returned future does not borrow it:
+    let timeout_fut = with_timeout(timer, fut, timeout);
+    async move {
+        match await!(timeout_fut) {
+            Ok(output) => output,
+            Err(TimeoutError) => {
+                warn!("Node operation timed out after {} seconds", timeout.as_secs());
+                timed_out.store(true, Ordering::SeqCst);
+                Err(NodeError::Timeout(timeout))
+            },
+        }
+    }.boxed()
+}
+
+/// A node capability, where every operation fails if it does not complete in time
+#[derive(Clone)]
+pub struct WithTimeout<T> {
+    inner: T,
+    timer: Timer,
+    opt_timeout: Option<Duration>,
+    timed_out: Arc<AtomicBool>,
+}
+
+impl<T> WithTimeout<T> {
+    fn new(inner: T, timer: Timer, opt_timeout: Option<Duration>, timed_out: Arc<AtomicBool>) -> Self {
+        WithTimeout {
+            inner,
+            timer,
+            opt_timeout,
+            timed_out,
+        }
+    }
+}
+
+impl<T: NodeReportSource> NodeReportSource for WithTimeout<T> {
+    fn incoming_reports(&mut self)
+        -> BoxFuture<'_, Result<(NodeReport, BoxStream<'static, Vec<NodeReportMutation>>), NodeError>> {
+        // Only obtaining the report is limited. Mutations may be awaited indefinitely.
+        guard(&self.timer, self.opt_timeout, self.timed_out.clone(), self.inner.incoming_reports())
+    }
+}
+
+impl<T: NodeConfig> NodeConfig for WithTimeout<T> {
+    fn add_relay(&mut self, named_relay_address: NamedRelayAddress)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        guard(&self.timer, self.opt_timeout, self.timed_out.clone(),
+              self.inner.add_relay(named_relay_address))
+    }
+
+    fn remove_relay(&mut self, relay_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        guard(&self.timer, self.opt_timeout, self.timed_out.clone(),
+              self.inner.remove_relay(relay_public_key))
+    }
+
+    fn add_index_server(&mut self, named_index_server_address: NamedIndexServerAddress)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        guard(&self.timer, self.opt_timeout, self.timed_out.clone(),
+              self.inner.add_index_server(named_index_server_address))
+    }
+
+    fn remove_index_server(&mut self, index_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        guard(&self.timer, self.opt_timeout, self.timed_out.clone(),
+              self.inner.remove_index_server(index_public_key))
+    }
+
+    fn add_friend(&mut self,
+                  friend_public_key: PublicKey,
+                  relays: Vec<RelayAddress>,
+                  name: String,
+                  balance: i128) -> BoxFuture<'_, Result<(), NodeError>> {
+        guard(&self.timer, self.opt_timeout, self.timed_out.clone(),
+              self.inner.add_friend(friend_public_key, relays, name, balance))
+    }
+
+    fn set_friend_relays(&mut self, friend_public_key: PublicKey, relays: Vec<RelayAddress>)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        guard(&self.timer, self.opt_timeout, self.timed_out.clone(),
+              self.inner.set_friend_relays(friend_public_key, relays))
+    }
+
+    fn remove_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        guard(&self.timer, self.opt_timeout, self.timed_out.clone(),
+              self.inner.remove_friend(friend_public_key))
+    }
+
+    fn enable_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        guard(&self.timer, self.opt_timeout, self.timed_out.clone(),
+              self.inner.enable_friend(friend_public_key))
+    }
+
+    fn disable_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        guard(&self.timer, self.opt_timeout, self.timed_out.clone(),
+              self.inner.disable_friend(friend_public_key))
+    }
+
+    fn open_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        guard(&self.timer, self.opt_timeout, self.timed_out.clone(),
+              self.inner.open_friend(friend_public_key))
+    }
+
+    fn close_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        guard(&self.timer, self.opt_timeout, self.timed_out.clone(),
+              self.inner.close_friend(friend_public_key))
+    }
+
+    fn set_friend_remote_max_debt(&mut self, friend_public_key: PublicKey, remote_max_debt: u128)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        guard(&self.timer, self.opt_timeout, self.timed_out.clone(),
+              self.inner.set_friend_remote_max_debt(friend_public_key, remote_max_debt))
+    }
+
+    fn reset_friend_channel(&mut self, friend_public_key: PublicKey, remote_reset_terms: ResetTermsReport)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        guard(&self.timer, self.opt_timeout, self.timed_out.clone(),
+              self.inner.reset_friend_channel(friend_public_key, remote_reset_terms))
+    }
+}
+
+impl<T: NodeRoutes> NodeRoutes for WithTimeout<T> {
+    fn request_routes(&mut self,
+                      capacity: u128,
+                      source: PublicKey,
+                      destination: PublicKey,
+                      opt_exclude: Option<(PublicKey, PublicKey)>)
+        -> BoxFuture<'_, Result<Vec<RouteWithCapacity>, NodeError>> {
+        guard(&self.timer, self.opt_timeout, self.timed_out.clone(),
+              self.inner.request_routes(capacity, source, destination, opt_exclude))
+    }
+}
+
+impl<T: NodeSendFunds> NodeSendFunds for WithTimeout<T> {
+    fn request_send_funds(&mut self,
+                          request_id: Uid,
+                          route: FriendsRoute,
+                          invoice_id: InvoiceId,
+                          dest_payment: u128) -> BoxFuture<'_, Result<Receipt, NodeError>> {
+        guard(&self.timer, self.opt_timeout, self.timed_out.clone(),
+              self.inner.request_send_funds(request_id, route, invoice_id, dest_payment))
+    }
+
+    fn receipt_ack(&mut self, request_id: Uid, receipt: Receipt)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        guard(&self.timer, self.opt_timeout, self.timed_out.clone(),
+              self.inner.receipt_ack(request_id, receipt))
+    }
+}
+
+/// A node connection where every remote operation is limited in time.
+/// An operation that times out fails with `NodeError::Timeout`.
+/// `timed_out()` tells whether this happened, even if the error was handled along the way.
+pub struct TimeoutNode<N: Node> {
+    report: WithTimeout<N::Report>,
+    opt_config: Option<WithTimeout<N::Config>>,
+    opt_routes: Option<WithTimeout<N::Routes>>,
+    opt_send_funds: Option<WithTimeout<N::SendFunds>>,
+    timed_out: Arc<AtomicBool>,
+}
+
+impl<N: Node> TimeoutNode<N> {
+    /// Wrap the capabilities of `node`, limiting operations using `timer`.
+    /// If `opt_timeout` is None, operations are not limited.
+    pub fn new(node: &mut N, timer: &Timer, opt_timeout: Option<Duration>) -> Self {
+        let timed_out = Arc::new(AtomicBool::new(false));
+
+        TimeoutNode {
+            report: WithTimeout::new(node.report().clone(), timer.clone(), opt_timeout, timed_out.clone()),
+            opt_config: node.config()
+                .map(|config| WithTimeout::new(config.clone(), timer.clone(), opt_timeout, timed_out.clone())),
+            opt_routes: node.routes()
+                .map(|routes| WithTimeout::new(routes.clone(), timer.clone(), opt_timeout, timed_out.clone())),
+            opt_send_funds: node.send_funds()
+                .map(|send_funds| WithTimeout::new(send_funds.clone(), timer.clone(), opt_timeout, timed_out.clone())),
+            timed_out,
+        }
+    }
+
+    /// Did any operation time out so far?
+    pub fn timed_out(&self) -> bool {
+        self.timed_out.load(Ordering::SeqCst)
+    }
+}
+
+impl<N: Node> Node for TimeoutNode<N> {
+    type Report = WithTimeout<N::Report>;
+    type Config = WithTimeout<N::Config>;
+    type Routes = WithTimeout<N::Routes>;
+    type SendFunds = WithTimeout<N::SendFunds>;
+
+    fn report(&mut self) -> &mut Self::Report {
+        &mut self.report
+    }
+
+    fn config(&mut self) -> Option<&mut Self::Config> {
+        self.opt_config.as_mut()
+    }
+
+    fn routes(&mut self) -> Option<&mut Self::Routes> {
+        self.opt_routes.as_mut()
+    }
+
+    fn send_funds(&mut self) -> Option<&mut Self::SendFunds> {
+        self.opt_send_funds.as_mut()
+    }
+}
@@ -0,0 +1,36 @@
+use clap::ErrorKind;
+
+use stctrl::cli::{build_app, build_shell_app, CommandSpec, COMMANDS};
+
+/// All command paths in `command_specs`, prefixed by `prefix`
+fn command_paths(prefix: &[&'static str], command_specs: &[CommandSpec]) -> Vec<Vec<&'static str>> {
+    let mut paths = Vec::new();
+    for command_spec in command_specs {
+        let mut path = prefix.to_vec();
+        path.push(command_spec.name);
+        paths.extend(command_paths(&path, command_spec.subcommands));
+        paths.push(path);
+    }
+    paths
+}
+
+#[test]
+fn test_commands_match_app() {
+    for path in command_paths(&[], COMMANDS) {
+        let mut args = vec!["stctrl"];
+        args.extend_from_slice(&path);
+        args.push("--help");
+        let e = build_app().get_matches_from_safe(args).unwrap_err();
+        assert_eq!(e.kind, ErrorKind::HelpDisplayed, "{:?}", path);
+    }
+}
+
+#[test]
+fn test_shell_app_rejects_connection_args() {
+    let args = ["stctrl", "--timeout", "5", "info", "balance"];
+    assert!(build_app().get_matches_from_safe(args.to_vec()).is_ok());
+    assert!(build_shell_app().get_matches_from_safe(args.to_vec()).is_err());
+
+    let args = ["stctrl", "--format", "json", "info", "balance"];
+    assert!(build_shell_app().get_matches_from_safe(args.to_vec()).is_ok());
+}
@@ -0,0 +1,442 @@
+//! An in-process fake node, allowing to run stctrl's commands without a network
+//! or a running offst node.
+//!
+//! The report is scripted by the test. Configuration calls and payments are recorded,
+//! and the routes and payment outcomes returned to the commands are configurable.
+
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use futures::{future, stream, FutureExt, StreamExt};
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+
+use clap::ArgMatches;
+
+use app::{NamedRelayAddress, NamedIndexServerAddress, RelayAddress, PublicKey, Receipt,
+    HashResult, Signature, HASH_RESULT_LEN, SIGNATURE_LEN,
+    string_to_public_key};
+use app::uid::Uid;
+use app::invoice::InvoiceId;
+use app::route::{FriendsRoute, RouteWithCapacity};
+use app::report::{NodeReport, NodeReportMutation, ResetTermsReport,
+    FunderReport, IndexClientReport, FriendReport, FriendLivenessReport,
+    FriendStatusReport, ChannelStatusReport, TcReport, DirectionReport,
+    McBalanceReport, McRequestsStatusReport, RequestsStatusReport,
+    SentLocalRelaysReport};
+
+use stctrl::cli::build_app;
+use stctrl::node::{Node, NodeError, AppError, NodeReportSource, NodeConfig, NodeRoutes, NodeSendFunds};
+
+/// A deterministic public key, for tests
+pub fn public_key(index: u8) -> PublicKey {
+    string_to_public_key(&base64::encode_config(&[index; 32], base64::URL_SAFE_NO_PAD))
+        .unwrap()
+}
+
+/// A report of a node without relays, index servers or friends
+pub fn empty_node_report(local_public_key: PublicKey) -> NodeReport {
+    NodeReport {
+        funder_report: FunderReport {
+            local_public_key,
+            relays: Vec::new(),
+            friends: Default::default(),
+            num_ready_receipts: 0,
+        },
+        index_client_report: IndexClientReport {
+            index_servers: Vec::new(),
+            opt_connected_server: None,
+        },
+    }
+}
+
+/// Add an enabled, online friend with a consistent channel
+pub fn add_friend(node_report: &mut NodeReport,
+                  name: &str,
+                  friend_public_key: PublicKey,
+                  balance: i128) {
+
+    let friend_report = FriendReport {
+        name: name.to_owned(),
+        remote_relays: Vec::new(),
+        sent_local_relays: SentLocalRelaysReport::NeverSent,
+        opt_last_incoming_move_token: None,
+        liveness: FriendLivenessReport::Online,
+        channel_status: ChannelStatusReport::Consistent(TcReport {
+            direction: DirectionReport::Incoming,
+            balance: McBalanceReport {
+                balance,
+                local_max_debt: 100,
+                remote_max_debt: 100,
+                local_pending_debt: 0,
+                remote_pending_debt: 0,
+            },
+            requests_status: McRequestsStatusReport {
+                local: RequestsStatusReport::Open,
+                remote: RequestsStatusReport::Open,
+            },
+            num_local_pending_requests: 0,
+            num_remote_pending_requests: 0,
+        }),
+        wanted_remote_max_debt: 100,
+        wanted_local_requests_status: RequestsStatusReport::Open,
+        num_pending_requests: 0,
+        num_pending_responses: 0,
+        status: FriendStatusReport::Enabled,
+        num_pending_user_requests: 0,
+    };
+    node_report.funder_report.friends.insert(friend_public_key, friend_report);
+}
+
+/// Add a relay to the report
+pub fn add_relay(node_report: &mut NodeReport, name: &str, relay_public_key: PublicKey) {
+    node_report.funder_report.relays.push(NamedRelayAddress {
+        public_key: relay_public_key,
+        address: "127.0.0.1:8000".to_owned(),
+        name: name.to_owned(),
+    });
+}
+
+/// Add an index server to the report
+pub fn add_index_server(node_report: &mut NodeReport, name: &str, index_public_key: PublicKey) {
+    node_report.index_client_report.index_servers.push(NamedIndexServerAddress {
+        public_key: index_public_key,
+        address: "127.0.0.1:9000".to_owned(),
+        name: name.to_owned(),
+    });
+}
+
+/// A route through the given nodes, with the given capacity
+pub fn route_with_capacity(public_keys: Vec<PublicKey>, capacity: u128) -> RouteWithCapacity {
+    RouteWithCapacity {
+        route: FriendsRoute { public_keys },
+        capacity,
+    }
+}
+
+/// Parse a command line. `args` should not include the program name.
+pub fn matches(args: &[&str]) -> ArgMatches<'static> {
+    let mut full_args = vec!["stctrl"];
+    full_args.extend_from_slice(args);
+    build_app().get_matches_from(full_args)
+}
+
+/// A call to the node's configuration interface
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigCall {
+    AddRelay(String, PublicKey),
+    RemoveRelay(PublicKey),
+    AddIndexServer(String, PublicKey),
+    RemoveIndexServer(PublicKey),
+    AddFriend(String, PublicKey, i128),
+    SetFriendRelays(PublicKey),
+    RemoveFriend(PublicKey),
+    EnableFriend(PublicKey),
+    DisableFriend(PublicKey),
+    OpenFriend(PublicKey),
+    CloseFriend(PublicKey),
+    SetFriendRemoteMaxDebt(PublicKey, u128),
+    ResetFriendChannel(PublicKey),
+}
+
+/// A payment request received by the fake node
+#[derive(Debug, Clone)]
+pub struct SendFundsCall {
+    pub route: Vec<PublicKey>,
+    pub invoice_id: InvoiceId,
+    pub dest_payment: u128,
+}
+
+#[derive(Clone)]
+pub struct MockReport {
+    node_report: Arc<Mutex<NodeReport>>,
+    mutations: Arc<Mutex<Vec<Vec<NodeReportMutation>>>>,
+    /// Never respond to report requests
+    unresponsive: bool,
+}
+
+impl NodeReportSource for MockReport {
+    fn incoming_reports(&mut self)
+        -> BoxFuture<'_, Result<(NodeReport, BoxStream<'static, Vec<NodeReportMutation>>), NodeError>> {
+
+        if self.unresponsive {
+            return future::pending().boxed();
+        }
+        let node_report = self.node_report.lock().unwrap().clone();
+        let mutations = self.mutations.lock().unwrap().drain(..).collect::<Vec<_>>();
+        future::ready(Ok((node_report, stream::iter(mutations).boxed()))).boxed()
+    }
+}
+
+#[derive(Clone)]
+pub struct MockConfig {
+    calls: Arc<Mutex<Vec<ConfigCall>>>,
+}
+
+impl MockConfig {
+    fn record(&mut self, config_call: ConfigCall) -> BoxFuture<'_, Result<(), NodeError>> {
+        self.calls.lock().unwrap().push(config_call);
+        future::ready(Ok(())).boxed()
+    }
+}
+
+impl NodeConfig for MockConfig {
+    fn add_relay(&mut self, named_relay_address: NamedRelayAddress)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        self.record(ConfigCall::AddRelay(named_relay_address.name, named_relay_address.public_key))
+    }
+
+    fn remove_relay(&mut self, relay_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        self.record(ConfigCall::RemoveRelay(relay_public_key))
+    }
+
+    fn add_index_server(&mut self, named_index_server_address: NamedIndexServerAddress)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        self.record(ConfigCall::AddIndexServer(named_index_server_address.name,
+                                               named_index_server_address.public_key))
+    }
+
+    fn remove_index_server(&mut self, index_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        self.record(ConfigCall::RemoveIndexServer(index_public_key))
+    }
+
+    fn add_friend(&mut self,
+                  friend_public_key: PublicKey,
+                  _relays: Vec<RelayAddress>,
+                  name: String,
+                  balance: i128) -> BoxFuture<'_, Result<(), NodeError>> {
+        self.record(ConfigCall::AddFriend(name, friend_public_key, balance))
+    }
+
+    fn set_friend_relays(&mut self, friend_public_key: PublicKey, _relays: Vec<RelayAddress>)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        self.record(ConfigCall::SetFriendRelays(friend_public_key))
+    }
+
+    fn remove_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        self.record(ConfigCall::RemoveFriend(friend_public_key))
+    }
+
+    fn enable_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        self.record(ConfigCall::EnableFriend(friend_public_key))
+    }
+
+    fn disable_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        self.record(ConfigCall::DisableFriend(friend_public_key))
+    }
+
+    fn open_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        self.record(ConfigCall::OpenFriend(friend_public_key))
+    }
+
+    fn close_friend(&mut self, friend_public_key: PublicKey)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        self.record(ConfigCall::CloseFriend(friend_public_key))
+    }
+
+    fn set_friend_remote_max_debt(&mut self, friend_public_key: PublicKey, remote_max_debt: u128)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        self.record(ConfigCall::SetFriendRemoteMaxDebt(friend_public_key, remote_max_debt))
+    }
+
+    fn reset_friend_channel(&mut self, friend_public_key: PublicKey, _remote_reset_terms: ResetTermsReport)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        self.record(ConfigCall::ResetFriendChannel(friend_public_key))
+    }
+}
+
+#[derive(Clone)]
+pub struct MockRoutes {
+    routes: Arc<Mutex<Vec<RouteWithCapacity>>>,
+    /// Excluded edges of every routes request
+    requests: Arc<Mutex<Vec<Option<(PublicKey, PublicKey)>>>>,
+}
+
+impl NodeRoutes for MockRoutes {
+    fn request_routes(&mut self,
+                      _capacity: u128,
+                      _source: PublicKey,
+                      destination: PublicKey,
+                      opt_exclude: Option<(PublicKey, PublicKey)>)
+        -> BoxFuture<'_, Result<Vec<RouteWithCapacity>, NodeError>> {
+
+        self.requests.lock().unwrap().push(opt_exclude.clone());
+        // Behave like an index server: Only return routes to the destination,
+        // that don't go through the excluded edge.
+        let routes = self.routes.lock().unwrap()
+            .iter()
+            .filter(|route_with_capacity| 
+                route_with_capacity.route.public_keys.last() == Some(&destination))
+            .filter(|route_with_capacity| match &opt_exclude {
+                Some((from, to)) => !route_with_capacity.route.public_keys
+                    .windows(2)
+                    .any(|pair| &pair[0] == from && &pair[1] == to),
+                None => true,
+            })
+            .cloned()
+            .collect();
+        future::ready(Ok(routes)).boxed()
+    }
+}
+
+#[derive(Clone)]
+pub struct MockSendFunds {
+    /// Outcomes of the next payments. Payments succeed once this queue is empty.
+    outcomes: Arc<Mutex<VecDeque<bool>>>,
+    calls: Arc<Mutex<Vec<SendFundsCall>>>,
+    num_receipt_acks: Arc<Mutex<usize>>,
+}
+
+impl NodeSendFunds for MockSendFunds {
+    fn request_send_funds(&mut self,
+                          _request_id: Uid,
+                          route: FriendsRoute,
+                          invoice_id: InvoiceId,
+                          dest_payment: u128) -> BoxFuture<'_, Result<Receipt, NodeError>> {
+
+        self.calls.lock().unwrap().push(SendFundsCall {
+            route: route.public_keys.clone(),
+            invoice_id: invoice_id.clone(),
+            dest_payment,
+        });
+
+        let success = self.outcomes.lock().unwrap().pop_front().unwrap_or(true);
+        let res = if success {
+            Ok(Receipt {
+                response_hash: HashResult::from(&[0; HASH_RESULT_LEN]),
+                invoice_id,
+                dest_payment,
+                signature: Signature::from(&[0; SIGNATURE_LEN]),
+            })
+        } else {
+            Err(NodeError::AppError(AppError::new(MockSendFundsError)))
+        };
+        future::ready(res).boxed()
+    }
+
+    fn receipt_ack(&mut self, _request_id: Uid, _receipt: Receipt)
+        -> BoxFuture<'_, Result<(), NodeError>> {
+        *self.num_receipt_acks.lock().unwrap() += 1;
+        future::ready(Ok(())).boxed()
+    }
+}
+
+/// The error of a failed payment
+#[derive(Debug)]
+pub struct MockSendFundsError;
+
+/// A fake node. By default the application has all permissions.
+pub struct MockNode {
+    report: MockReport,
+    opt_config: Option<MockConfig>,
+    opt_routes: Option<MockRoutes>,
+    opt_send_funds: Option<MockSendFunds>,
+}
+
+impl MockNode {
+    pub fn new(node_report: NodeReport) -> Self {
+        MockNode {
+            report: MockReport {
+                node_report: Arc::new(Mutex::new(node_report)),
+                mutations: Arc::new(Mutex::new(Vec::new())),
+                unresponsive: false,
+            },
+            opt_config: Some(MockConfig {
+                calls: Arc::new(Mutex::new(Vec::new())),
+            }),
+            opt_routes: Some(MockRoutes {
+                routes: Arc::new(Mutex::new(Vec::new())),
+                requests: Arc::new(Mutex::new(Vec::new())),
+            }),
+            opt_send_funds: Some(MockSendFunds {
+                outcomes: Arc::new(Mutex::new(VecDeque::new())),
+                calls: Arc::new(Mutex::new(Vec::new())),
+                num_receipt_acks: Arc::new(Mutex::new(0)),
+            }),
+        }
+    }
+
+    pub fn without_config(mut self) -> Self {
+        self.opt_config = None;
+        self
+    }
+
+    pub fn without_routes(mut self) -> Self {
+        self.opt_routes = None;
+        self
+    }
+
+    pub fn without_send_funds(mut self) -> Self {
+        self.opt_send_funds = None;
+        self
+    }
+
+    /// A node that never sends a report
+    pub fn unresponsive(mut self) -> Self {
+        self.report.unresponsive = true;
+        self
+    }
+
+    /// Mutations sent together with the next report
+    pub fn push_mutations(&self, mutations: Vec<NodeReportMutation>) {
+        self.report.mutations.lock().unwrap().push(mutations);
+    }
+
+    /// Routes returned by the fake index server
+    pub fn set_routes(&self, routes: Vec<RouteWithCapacity>) {
+        *self.opt_routes.as_ref().unwrap().routes.lock().unwrap() = routes;
+    }
+
+    /// Set the outcome of the next payment
+    pub fn push_send_outcome(&self, success: bool) {
+        self.opt_send_funds.as_ref().unwrap().outcomes.lock().unwrap().push_back(success);
+    }
+
+    pub fn config_calls(&self) -> Vec<ConfigCall> {
+        self.opt_config.as_ref().unwrap().calls.lock().unwrap().clone()
+    }
+
+    /// Excluded edges of all routes requests so far
+    pub fn routes_requests(&self) -> Vec<Option<(PublicKey, PublicKey)>> {
+        self.opt_routes.as_ref().unwrap().requests.lock().unwrap().clone()
+    }
+
+    pub fn send_funds_calls(&self) -> Vec<SendFundsCall> {
+        self.opt_send_funds.as_ref().unwrap().calls.lock().unwrap().clone()
+    }
+
+    pub fn num_receipt_acks(&self) -> usize {
+        *self.opt_send_funds.as_ref().unwrap().num_receipt_acks.lock().unwrap()
+    }
+}
+
+impl Node for MockNode {
+    type Report = MockReport;
+    type Config = MockConfig;
+    type Routes = MockRoutes;
+    type SendFunds = MockSendFunds;
+
+    fn report(&mut self) -> &mut MockReport {
+        &mut self.report
+    }
+
+    fn config(&mut self) -> Option<&mut MockConfig> {
+        self.opt_config.as_mut()
+    }
+
+    fn routes(&mut self) -> Option<&mut MockRoutes> {
+        self.opt_routes.as_mut()
+    }
+
+    fn send_funds(&mut self) -> Option<&mut MockSendFunds> {
+        self.opt_send_funds.as_mut()
+    }
+}
@@ -0,0 +1,178 @@
+#![feature(futures_api, async_await, await_macro)]
+
+mod common;
+
+use std::fs;
+
+use futures::executor::block_on;
+use tempdir::TempDir;
+
+use app::{store_friend_to_file, FriendAddress};
+use app::report::NodeReport;
+
+use stctrl::config::{config, plan_node_config, ApplyParams, ConfigError, ConfigOp};
+
+use common::{MockNode, ConfigCall, matches, public_key, empty_node_report, add_friend, add_relay};
+
+fn report_with_friends() -> NodeReport {
+    let mut node_report = empty_node_report(public_key(0));
+    add_friend(&mut node_report, "alice", public_key(1), 20);
+    add_friend(&mut node_report, "bob", public_key(2), -5);
+    add_relay(&mut node_report, "relay0", public_key(10));
+    node_report
+}
+
+fn node_with_friends() -> MockNode {
+    MockNode::new(report_with_friends())
+}
+
+/// Plan applying a node configuration file with the given contents
+fn plan_apply(file_data: &str, prune_friends: bool) -> Result<Vec<ConfigOp>, ConfigError> {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let node_config_pathbuf = temp_dir.path().join("node.toml");
+    fs::write(&node_config_pathbuf, file_data).unwrap();
+    let params = ApplyParams {
+        node_config_pathbuf,
+        prune_friends,
+    };
+    plan_node_config(&params, &report_with_friends())
+}
+
+fn num_remove_friend_ops(config_ops: &[ConfigOp]) -> usize {
+    config_ops
+        .iter()
+        .filter(|config_op| match config_op {
+            ConfigOp::RemoveFriend(..) => true,
+            _ => false,
+        })
+        .count()
+}
+
+fn run_config(node: &mut MockNode, args: &[&str]) -> Result<(), ConfigError> {
+    let mut full_args = vec!["config"];
+    full_args.extend_from_slice(args);
+    let matches = matches(&full_args);
+    let config_matches = matches.subcommand_matches("config").unwrap();
+    block_on(config(config_matches, node))
+}
+
+#[test]
+fn test_config_friend_operations() {
+    let mut node = node_with_friends();
+
+    run_config(&mut node, &["disable-friend", "--name", "alice"]).unwrap();
+    run_config(&mut node, &["close-friend", "--name", "bob"]).unwrap();
+    run_config(&mut node, &["set-friend-max-debt", "--name", "alice", "--mdebt", "200"]).unwrap();
+    run_config(&mut node, &["remove-friend", "--name", "bob"]).unwrap();
+
+    assert_eq!(node.config_calls(), vec![
+        ConfigCall::DisableFriend(public_key(1)),
+        ConfigCall::CloseFriend(public_key(2)),
+        ConfigCall::SetFriendRemoteMaxDebt(public_key(1), 200),
+        ConfigCall::RemoveFriend(public_key(2)),
+    ]);
+}
+
+#[test]
+fn test_config_remove_relay() {
+    let mut node = node_with_friends();
+    run_config(&mut node, &["remove-relay", "--name", "relay0"]).unwrap();
+    assert_eq!(node.config_calls(), vec![ConfigCall::RemoveRelay(public_key(10))]);
+}
+
+#[test]
+fn test_config_dry_run() {
+    let mut node = node_with_friends();
+    run_config(&mut node, &["remove-friend", "--name", "alice", "--dry-run"]).unwrap();
+    assert!(node.config_calls().is_empty());
+}
+
+#[test]
+fn test_config_friend_not_found() {
+    let mut node = node_with_friends();
+    match run_config(&mut node, &["enable-friend", "--name", "carol"]) {
+        Err(ConfigError::FriendNameNotFound(_)) => {},
+        res => panic!("Unexpected result: {:?}", res),
+    }
+    assert!(node.config_calls().is_empty());
+}
+
+#[test]
+fn test_config_reset_consistent_channel() {
+    let mut node = node_with_friends();
+    match run_config(&mut node, &["reset-friend", "--name", "alice"]) {
+        Err(ConfigError::ChannelNotInconsistent(_)) => {},
+        res => panic!("Unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn test_config_no_permissions() {
+    let mut node = node_with_friends().without_config();
+    match run_config(&mut node, &["remove-friend", "--name", "alice"]) {
+        Err(ConfigError::NoPermissions) => {},
+        res => panic!("Unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn test_config_apply_missing_sections() {
+    // No friends section: Friends are not managed by the file, even when pruning:
+    let config_ops = plan_apply("relays = []\n", true).unwrap();
+    assert_eq!(num_remove_friend_ops(&config_ops), 0);
+    // The relays section is present, so unlisted relays are removed:
+    assert_eq!(config_ops.len(), 1);
+    match &config_ops[0] {
+        ConfigOp::RemoveRelay(name, _) => assert_eq!(name, "relay0"),
+        config_op => panic!("Unexpected operation: {:?}", config_op),
+    }
+
+    // An empty file changes nothing:
+    assert!(plan_apply("", true).unwrap().is_empty());
+}
+
+#[test]
+fn test_config_apply_prune_friends() {
+    // Friends are only removed on request:
+    let config_ops = plan_apply("friends = []\n", false).unwrap();
+    assert_eq!(num_remove_friend_ops(&config_ops), 0);
+
+    let config_ops = plan_apply("friends = []\n", true).unwrap();
+    assert_eq!(num_remove_friend_ops(&config_ops), 2);
+}
+
+#[test]
+fn test_config_apply_unknown_section() {
+    match plan_apply("[[freinds]]\nname = \"alice\"\n", true) {
+        Err(ConfigError::ParseNodeConfigFileError(_)) => {},
+        res => panic!("Unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn test_config_apply_friend_name_taken() {
+    // A friend file for a new friend, named like the existing friend alice:
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let ticket_path = temp_dir.path().join("new_alice.friend");
+    let friend_address = FriendAddress {
+        public_key: public_key(5),
+        relays: Vec::new(),
+    };
+    store_friend_to_file(&friend_address, &ticket_path).unwrap();
+    let file_data = format!("[[friends]]\nname = \"alice\"\nticket = {:?}\n", 
+                            ticket_path.to_str().unwrap());
+
+    match plan_apply(&file_data, false) {
+        Err(ConfigError::FriendNameAlreadyExists(name)) => assert_eq!(name, "alice"),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+
+    // When pruning, the existing alice is removed before the new one is added:
+    let config_ops = plan_apply(&file_data, true).unwrap();
+    assert_eq!(num_remove_friend_ops(&config_ops), 2);
+    assert!(config_ops.iter().any(|config_op| match config_op {
+        ConfigOp::AddFriend(name, friend_public_key, _, _) => 
+            name == "alice" && friend_public_key == &public_key(5),
+        _ => false,
+    }));
+}
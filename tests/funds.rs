@@ -0,0 +1,468 @@
+#![feature(futures_api, async_await, await_macro)]
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use futures::executor::block_on;
+use tempdir::TempDir;
+
+use app::{public_key_to_string, store_friend_to_file, FriendAddress};
+use app::invoice::{InvoiceId, INVOICE_ID_LEN};
+use app::report::NodeReport;
+
+use stctrl::funds::{funds, send_funds, FundsError, SendFundsParams, PaymentPart, 
+                    AttemptStatus, RouteStrategy};
+use stctrl::invoice::load_invoice_from_file;
+use stctrl::ledger::load_ledger;
+use stctrl::node::{Node, NodeError, AppError};
+use stctrl::profile::Profile;
+
+use common::{MockNode, MockSendFundsError, matches, public_key, empty_node_report, add_friend,
+    route_with_capacity};
+
+/// Our node (0) has two friends, alice (1) and bob (3).
+fn report_with_friends() -> NodeReport {
+    let mut node_report = empty_node_report(public_key(0));
+    add_friend(&mut node_report, "alice", public_key(1), 20);
+    add_friend(&mut node_report, "bob", public_key(3), 0);
+    node_report
+}
+
+/// Both friends can forward payments to a remote node (2).
+fn node_with_routes() -> MockNode {
+    let node = MockNode::new(report_with_friends());
+    node.set_routes(vec![
+        route_with_capacity(vec![public_key(0), public_key(1), public_key(2)], 100),
+        route_with_capacity(vec![public_key(0), public_key(3), public_key(2)], 100),
+        route_with_capacity(vec![public_key(0), public_key(1)], 100),
+    ]);
+    node
+}
+
+fn run_funds(node: &mut MockNode, data_dir: &Path, args: &[&str]) -> Result<(), FundsError> {
+    let data_dir_str = data_dir.to_str().unwrap();
+    let mut full_args = vec!["--data-dir", data_dir_str, "funds"];
+    full_args.extend_from_slice(args);
+    let matches = matches(&full_args);
+    let funds_matches = matches.subcommand_matches("funds").unwrap();
+    block_on(funds(funds_matches, &Profile::default(), node))
+}
+
+fn num_receipts(data_dir: &Path) -> usize {
+    match fs::read_dir(data_dir.join("receipts")) {
+        Ok(read_dir) => read_dir.count(),
+        Err(_) => 0,
+    }
+}
+
+#[test]
+fn test_funds_send() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes();
+    let destination = public_key_to_string(&public_key(2));
+
+    run_funds(&mut node, temp_dir.path(), &["send", "-d", &destination, "-a", "10"]).unwrap();
+
+    let send_funds_calls = node.send_funds_calls();
+    assert_eq!(send_funds_calls.len(), 1);
+    assert_eq!(send_funds_calls[0].dest_payment, 10);
+    assert_eq!(send_funds_calls[0].route.len(), 3);
+    assert_eq!(node.num_receipt_acks(), 1);
+    assert_eq!(num_receipts(temp_dir.path()), 1);
+
+    let ledger_entries = load_ledger(&temp_dir.path().join("ledger.jsonl")).unwrap();
+    assert_eq!(ledger_entries.len(), 1);
+    assert_eq!(ledger_entries[0].status, "success");
+    assert_eq!(ledger_entries[0].amount, "10");
+    assert_eq!(ledger_entries[0].fees, "1");
+
+    run_funds(&mut node, temp_dir.path(), &["history", "--status", "success"]).unwrap();
+    run_funds(&mut node, temp_dir.path(), &["history", "-d", "alice"]).unwrap();
+}
+
+#[test]
+fn test_funds_send_profile_data_dir() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes();
+    let destination = public_key_to_string(&public_key(2));
+
+    let profile = Profile {
+        data_dir: Some(temp_dir.path().to_path_buf()),
+        ..Profile::default()
+    };
+    let matches = matches(&["funds", "send", "-d", &destination, "-a", "10"]);
+    let funds_matches = matches.subcommand_matches("funds").unwrap();
+    block_on(funds(funds_matches, &profile, &mut node)).unwrap();
+
+    assert_eq!(num_receipts(temp_dir.path()), 1);
+}
+
+#[test]
+fn test_profile_setting_or_env() {
+    let default_profile = Profile::default();
+    assert_eq!(default_profile.setting_or_env(Some(1), Some(2)), Some(2));
+    assert_eq!(default_profile.setting_or_env(Some(1), None), Some(1));
+
+    let explicit_profile = Profile {
+        explicit: true,
+        ..Profile::default()
+    };
+    assert_eq!(explicit_profile.setting_or_env(Some(1), Some(2)), Some(1));
+    assert_eq!(explicit_profile.setting_or_env(None, Some(2)), Some(2));
+}
+
+#[test]
+fn test_funds_send_to_friend_name() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes();
+
+    run_funds(&mut node, temp_dir.path(), &["send", "-d", "alice", "-a", "5"]).unwrap();
+
+    let send_funds_calls = node.send_funds_calls();
+    assert_eq!(send_funds_calls.len(), 1);
+    assert_eq!(send_funds_calls[0].route, vec![public_key(0), public_key(1)]);
+}
+
+#[test]
+fn test_funds_send_to_ticket_file() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes();
+
+    // A corrupt address book should not matter when paying to a ticket file:
+    fs::write(temp_dir.path().join("address_book.toml"), "not a valid address book [").unwrap();
+    let ticket_path = temp_dir.path().join("remote.ticket");
+    let friend_address = FriendAddress {
+        public_key: public_key(2),
+        relays: Vec::new(),
+    };
+    store_friend_to_file(&friend_address, &ticket_path).unwrap();
+
+    run_funds(&mut node, temp_dir.path(), 
+              &["send", "-d", ticket_path.to_str().unwrap(), "-a", "5"]).unwrap();
+
+    let send_funds_calls = node.send_funds_calls();
+    assert_eq!(send_funds_calls.len(), 1);
+    assert_eq!(send_funds_calls[0].route.last(), Some(&public_key(2)));
+}
+
+#[test]
+fn test_funds_send_retry() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes();
+    let destination = public_key_to_string(&public_key(2));
+
+    node.push_send_outcome(false);
+    run_funds(&mut node, temp_dir.path(), 
+              &["send", "-d", &destination, "-a", "10", "--attempts", "2"]).unwrap();
+
+    // The second attempt should use the other candidate route. 
+    // The first hop of the failed route is excluded at the index server:
+    let send_funds_calls = node.send_funds_calls();
+    assert_eq!(send_funds_calls.len(), 2);
+    assert_ne!(send_funds_calls[1].route, send_funds_calls[0].route);
+    let failed_route = &send_funds_calls[0].route;
+    let failed_edge = (failed_route[0].clone(), failed_route[1].clone());
+    assert_eq!(node.routes_requests(), vec![None, Some(failed_edge)]);
+    assert_eq!(node.num_receipt_acks(), 1);
+
+    let ledger_entries = load_ledger(&temp_dir.path().join("ledger.jsonl")).unwrap();
+    let statuses: Vec<&str> = ledger_entries
+        .iter()
+        .map(|ledger_entry| ledger_entry.status.as_str())
+        .collect();
+    assert_eq!(statuses, vec!["failure", "success"]);
+}
+
+#[test]
+fn test_send_funds_attempts() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes();
+    let node_report = report_with_friends();
+    let mut app_routes = node.routes().unwrap().clone();
+    let mut app_send_funds = node.send_funds().unwrap().clone();
+
+    let params = SendFundsParams {
+        destination: public_key(2),
+        amount: 10,
+        invoice_id: InvoiceId::from(&[0; INVOICE_ID_LEN]),
+        route_strategy: RouteStrategy::default(),
+        avoid: Vec::new(),
+        split: false,
+        opt_max_fee: None,
+        max_attempts: 2,
+        opt_deadline: None,
+        data_dir: temp_dir.path().to_path_buf(),
+        opt_receipt_out: None,
+    };
+
+    node.push_send_outcome(false);
+    let mut approve = |_payment_parts: &[PaymentPart], _planned_fees: u128| Ok(true);
+    let send_funds_output = block_on(send_funds(&params,
+                                                &node_report,
+                                                &mut app_routes,
+                                                &mut app_send_funds,
+                                                &mut approve)).unwrap();
+
+    let statuses: Vec<AttemptStatus> = send_funds_output.attempts
+        .iter()
+        .map(|attempt| attempt.status.clone())
+        .collect();
+    let failure = AttemptStatus::Failure(NodeError::AppError(AppError::new(MockSendFundsError)));
+    assert_eq!(statuses, vec![failure, AttemptStatus::Success]);
+    assert_eq!(send_funds_output.receipts.len(), 1);
+}
+
+#[test]
+fn test_send_funds_split_receipt_out() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes();
+    let node_report = report_with_friends();
+    let mut app_routes = node.routes().unwrap().clone();
+    let mut app_send_funds = node.send_funds().unwrap().clone();
+
+    // Every part of a split payment has its own receipt:
+    let params = SendFundsParams {
+        destination: public_key(2),
+        amount: 10,
+        invoice_id: InvoiceId::from(&[0; INVOICE_ID_LEN]),
+        route_strategy: RouteStrategy::default(),
+        avoid: Vec::new(),
+        split: true,
+        opt_max_fee: None,
+        max_attempts: 1,
+        opt_deadline: None,
+        data_dir: temp_dir.path().to_path_buf(),
+        opt_receipt_out: Some(temp_dir.path().join("receipt.toml")),
+    };
+
+    let mut approve = |_payment_parts: &[PaymentPart], _planned_fees: u128| Ok(true);
+    match block_on(send_funds(&params, &node_report, &mut app_routes, &mut app_send_funds, &mut approve)) {
+        Err(FundsError::SplitReceiptOut) => {},
+        res => panic!("Unexpected result: {:?}", res),
+    }
+    assert!(node.send_funds_calls().is_empty());
+}
+
+#[test]
+fn test_funds_send_retry_routes_exhausted() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes();
+    let destination = public_key_to_string(&public_key(2));
+
+    // There are only two candidate routes to the destination:
+    node.push_send_outcome(false);
+    node.push_send_outcome(false);
+    match run_funds(&mut node, temp_dir.path(), 
+                    &["send", "-d", &destination, "-a", "10", "--attempts", "5"]) {
+        Err(FundsError::AttemptsFailed(attempts)) => assert_eq!(attempts.len(), 2),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+    assert_eq!(node.send_funds_calls().len(), 2);
+    assert_eq!(node.num_receipt_acks(), 0);
+}
+
+#[test]
+fn test_funds_send_retry_no_suitable_route() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = MockNode::new(report_with_friends());
+    // The route through bob can not carry the payment:
+    node.set_routes(vec![
+        route_with_capacity(vec![public_key(0), public_key(1), public_key(2)], 100),
+        route_with_capacity(vec![public_key(0), public_key(3), public_key(2)], 5),
+    ]);
+    let destination = public_key_to_string(&public_key(2));
+
+    // The failed attempt is reported, rather than the lack of a suitable route:
+    node.push_send_outcome(false);
+    match run_funds(&mut node, temp_dir.path(), 
+                    &["send", "-d", &destination, "-a", "10", "--attempts", "3"]) {
+        Err(FundsError::AttemptsFailed(attempts)) => assert_eq!(attempts.len(), 1),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+    assert_eq!(node.send_funds_calls().len(), 1);
+}
+
+#[test]
+fn test_funds_send_failure() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes();
+    let destination = public_key_to_string(&public_key(2));
+
+    node.push_send_outcome(false);
+    match run_funds(&mut node, temp_dir.path(), &["send", "-d", &destination, "-a", "10"]) {
+        Err(FundsError::AttemptsFailed(attempts)) => assert_eq!(attempts.len(), 1),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+    assert_eq!(node.num_receipt_acks(), 0);
+    assert_eq!(num_receipts(temp_dir.path()), 0);
+}
+
+#[test]
+fn test_funds_send_max_fee() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes();
+    node.set_routes(vec![
+        route_with_capacity(vec![public_key(0), public_key(1), public_key(3), public_key(2)], 100),
+    ]);
+    let destination = public_key_to_string(&public_key(2));
+
+    match run_funds(&mut node, temp_dir.path(), 
+                    &["send", "-d", &destination, "-a", "10", "--max-fee", "1"]) {
+        Err(FundsError::NoSuitableRoute) => {},
+        res => panic!("Unexpected result: {:?}", res),
+    }
+    assert!(node.send_funds_calls().is_empty());
+}
+
+#[test]
+fn test_funds_send_dry_run() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes();
+    let destination = public_key_to_string(&public_key(2));
+
+    run_funds(&mut node, temp_dir.path(), 
+              &["send", "-d", &destination, "-a", "10", "--dry-run"]).unwrap();
+    assert!(node.send_funds_calls().is_empty());
+    assert!(load_ledger(&temp_dir.path().join("ledger.jsonl")).unwrap().is_empty());
+}
+
+#[test]
+fn test_funds_send_receipt_not_stored() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes();
+    let destination = public_key_to_string(&public_key(2));
+
+    // The receipts directory can not be created:
+    fs::write(temp_dir.path().join("receipts"), "").unwrap();
+
+    // The payment itself succeeded, so it is recorded and acknowledged:
+    run_funds(&mut node, temp_dir.path(), &["send", "-d", &destination, "-a", "10"]).unwrap();
+    assert_eq!(node.send_funds_calls().len(), 1);
+    assert_eq!(node.num_receipt_acks(), 1);
+
+    let ledger_entries = load_ledger(&temp_dir.path().join("ledger.jsonl")).unwrap();
+    assert_eq!(ledger_entries.len(), 1);
+    assert_eq!(ledger_entries[0].status, "success");
+}
+
+#[test]
+fn test_funds_pay_invoice() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes();
+    let destination = public_key_to_string(&public_key(2));
+    let invoice_path = temp_dir.path().join("invoice.toml");
+    let invoice_path_str = invoice_path.to_str().unwrap();
+
+    run_funds(&mut node, temp_dir.path(), 
+              &["create-invoice", "-a", "15", "-d", &destination, "-o", invoice_path_str]).unwrap();
+    run_funds(&mut node, temp_dir.path(), &["send", "--invoice", invoice_path_str]).unwrap();
+
+    let invoice = load_invoice_from_file(&invoice_path).unwrap();
+    let send_funds_calls = node.send_funds_calls();
+    assert_eq!(send_funds_calls.len(), 1);
+    assert_eq!(send_funds_calls[0].dest_payment, 15);
+    assert_eq!(send_funds_calls[0].invoice_id, invoice.invoice_id);
+}
+
+#[test]
+fn test_funds_dry_run_writes_no_files() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes();
+    let destination = public_key_to_string(&public_key(2));
+    let invoice_path = temp_dir.path().join("invoice.toml");
+
+    run_funds(&mut node, temp_dir.path(), 
+              &["create-invoice", "-a", "15", "-d", &destination, 
+                "-o", invoice_path.to_str().unwrap(), "--dry-run"]).unwrap();
+    assert!(!invoice_path.exists());
+
+    run_funds(&mut node, temp_dir.path(), 
+              &["contacts", "add", "--name", "carol", "-d", &destination, "--dry-run"]).unwrap();
+    assert!(!temp_dir.path().join("address_book.toml").exists());
+
+    // Dry runs still validate their arguments:
+    match run_funds(&mut node, temp_dir.path(), 
+                    &["contacts", "remove", "--name", "carol", "--dry-run"]) {
+        Err(FundsError::ContactNameNotFound(name)) => assert_eq!(name, "carol"),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn test_funds_send_batch() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes();
+    let destination = public_key_to_string(&public_key(2));
+
+    let batch_path = temp_dir.path().join("batch.csv");
+    fs::write(&batch_path, format!("destination,amount,invoice_id,memo\n\
+                                    {},10,,first\n\
+                                    alice,5,,second\n", destination)).unwrap();
+    let results_path = temp_dir.path().join("results.json");
+
+    run_funds(&mut node, temp_dir.path(), 
+              &["send-batch", "--file", batch_path.to_str().unwrap(), 
+                "--output", results_path.to_str().unwrap()]).unwrap();
+
+    assert_eq!(node.send_funds_calls().len(), 2);
+    let results: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(&results_path).unwrap()).unwrap();
+    let results = results.as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    for result in results {
+        assert_eq!(result["status"], "success");
+    }
+}
+
+#[test]
+fn test_funds_send_batch_dry_run() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes();
+    let destination = public_key_to_string(&public_key(2));
+
+    // The first row is invalid. The dry run should still preview the second row:
+    let batch_path = temp_dir.path().join("batch.csv");
+    fs::write(&batch_path, format!("destination,amount,invoice_id,memo\n\
+                                    {},abc,,first\n\
+                                    alice,5,,second\n", destination)).unwrap();
+    let results_path = temp_dir.path().join("results.json");
+
+    match run_funds(&mut node, temp_dir.path(), 
+                    &["send-batch", "--file", batch_path.to_str().unwrap(), 
+                      "--output", results_path.to_str().unwrap(), "--dry-run"]) {
+        Err(FundsError::BatchPaymentsFailed(1)) => {},
+        res => panic!("Unexpected result: {:?}", res),
+    }
+
+    assert!(node.send_funds_calls().is_empty());
+    let results: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(&results_path).unwrap()).unwrap();
+    let results = results.as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["status"], "failure");
+    assert_eq!(results[1]["status"], "planned");
+}
+
+#[test]
+fn test_funds_routes() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes();
+    let destination = public_key_to_string(&public_key(2));
+
+    run_funds(&mut node, temp_dir.path(), &["routes", "-d", &destination, "-a", "10"]).unwrap();
+    assert!(node.send_funds_calls().is_empty());
+}
+
+#[test]
+fn test_funds_no_permissions() {
+    let temp_dir = TempDir::new("stctrl_test").unwrap();
+    let mut node = node_with_routes().without_send_funds();
+    let destination = public_key_to_string(&public_key(2));
+
+    match run_funds(&mut node, temp_dir.path(), &["send", "-d", &destination, "-a", "10"]) {
+        Err(FundsError::NoFundsPermissions) => {},
+        res => panic!("Unexpected result: {:?}", res),
+    }
+}
@@ -0,0 +1,160 @@
+#![feature(futures_api, async_await, await_macro)]
+
+mod common;
+
+use futures::executor::block_on;
+
+use stctrl::info::{info, InfoError, IndexClientState, index_client_state, index_info,
+                   node_ticket, last_friend_token, balance_info};
+use stctrl::profile::Profile;
+
+use common::{MockNode, matches, public_key, empty_node_report, add_friend, add_relay,
+    add_index_server};
+
+fn node_with_friends() -> MockNode {
+    let mut node_report = empty_node_report(public_key(0));
+    add_friend(&mut node_report, "alice", public_key(1), 20);
+    add_friend(&mut node_report, "bob", public_key(2), -5);
+    add_relay(&mut node_report, "relay0", public_key(10));
+    MockNode::new(node_report)
+}
+
+#[test]
+fn test_info_commands() {
+    let mut node = node_with_friends();
+    for args in &[&["info", "relays"][..],
+                  &["info", "index"],
+                  &["--format", "csv", "info", "index"],
+                  &["--format", "json", "info", "index"],
+                  &["info", "friends"],
+                  &["info", "friend", "--name", "alice"],
+                  &["info", "balance"],
+                  &["--format", "json", "info", "friends"],
+                  &["--format", "csv", "info", "balance"],
+                  &["--format", "csv", "info", "friends"],
+                  &["--format", "csv", "info", "friend", "--name", "alice"]] {
+        let matches = matches(args);
+        let info_matches = matches.subcommand_matches("info").unwrap();
+        block_on(info(info_matches, &Profile::default(), &mut node)).unwrap();
+    }
+}
+
+#[test]
+fn test_info_friend_not_found() {
+    let mut node = node_with_friends();
+    let matches = matches(&["info", "friend", "--name", "carol"]);
+    let info_matches = matches.subcommand_matches("info").unwrap();
+    match block_on(info(info_matches, &Profile::default(), &mut node)) {
+        Err(InfoError::FriendNameNotFound(_)) => {},
+        res => panic!("Unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn test_info_watch_mutations_closed() {
+    // The fake node closes the mutations stream once all scripted mutations were sent:
+    let mut node = node_with_friends();
+    let matches = matches(&["info", "watch"]);
+    let info_matches = matches.subcommand_matches("info").unwrap();
+    match block_on(info(info_matches, &Profile::default(), &mut node)) {
+        Err(InfoError::ReportMutationsClosed) => {},
+        res => panic!("Unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn test_balance_info() {
+    let mut node_report = empty_node_report(public_key(0));
+    add_friend(&mut node_report, "alice", public_key(1), 20);
+    add_friend(&mut node_report, "bob", public_key(2), -5);
+    add_friend(&mut node_report, "carol", public_key(3), 30);
+
+    let balance_info = balance_info(&node_report).unwrap();
+    assert_eq!(balance_info.total_balance, "45");
+    assert_eq!(balance_info.consistent_balance, "45");
+    assert_eq!(balance_info.inconsistent_balance, "0");
+    assert_eq!(balance_info.local_max_debt, "300");
+    assert_eq!(balance_info.remote_max_debt, "300");
+    assert_eq!(balance_info.spendable, "345");
+    assert_eq!(balance_info.receivable, "255");
+
+    // (name, balance, spendable, receivable). Every friend has max debts of 100:
+    let expected = [("alice", "20", "120", "80"),
+                    ("bob", "-5", "95", "105"),
+                    ("carol", "30", "130", "70")];
+    assert_eq!(balance_info.friends.len(), expected.len());
+    for (name, balance, spendable, receivable) in &expected {
+        let friend = balance_info.friends
+            .iter()
+            .find(|friend| friend.name == *name)
+            .unwrap();
+        assert!(friend.consistent);
+        assert_eq!(friend.balance, *balance);
+        assert_eq!(friend.spendable.as_ref().unwrap(), *spendable);
+        assert_eq!(friend.receivable.as_ref().unwrap(), *receivable);
+        assert_eq!(friend.local_max_debt.as_ref().unwrap(), "100");
+        assert_eq!(friend.remote_max_debt.as_ref().unwrap(), "100");
+    }
+}
+
+#[test]
+fn test_index_client_state() {
+    let mut node_report = empty_node_report(public_key(0));
+    assert_eq!(index_client_state(&node_report), IndexClientState::Disconnected);
+
+    add_index_server(&mut node_report, "index0", public_key(20));
+    assert_eq!(index_client_state(&node_report), IndexClientState::Connecting);
+
+    node_report.index_client_report.opt_connected_server = Some(public_key(20));
+    assert_eq!(index_client_state(&node_report), IndexClientState::Connected);
+}
+
+#[test]
+fn test_index_info() {
+    let mut node_report = empty_node_report(public_key(0));
+    add_index_server(&mut node_report, "index0", public_key(20));
+    add_index_server(&mut node_report, "index1", public_key(21));
+
+    let info = index_info(&node_report);
+    assert_eq!(info.state, IndexClientState::Connecting);
+    assert_eq!(info.connected_server, None);
+    assert_eq!(info.index_servers.len(), 2);
+
+    node_report.index_client_report.opt_connected_server = Some(public_key(21));
+    let info = index_info(&node_report);
+    assert_eq!(info.state, IndexClientState::Connected);
+    assert_eq!(info.connected_server, Some(info.index_servers[1].public_key.clone()));
+    assert!(!info.index_servers[0].connected);
+    assert!(info.index_servers[1].connected);
+}
+
+#[test]
+fn test_info_index_watch_mutations_closed() {
+    let mut node = node_with_friends();
+    let matches = matches(&["info", "index", "--watch"]);
+    let info_matches = matches.subcommand_matches("info").unwrap();
+    match block_on(info(info_matches, &Profile::default(), &mut node)) {
+        Err(InfoError::ReportMutationsClosed) => {},
+        res => panic!("Unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn test_node_ticket() {
+    let mut node_report = empty_node_report(public_key(0));
+    add_relay(&mut node_report, "relay0", public_key(10));
+
+    let node_address = node_ticket(&node_report);
+    assert_eq!(node_address.public_key, public_key(0));
+    assert_eq!(node_address.relays.len(), 1);
+    assert_eq!(node_address.relays[0].public_key, public_key(10));
+}
+
+#[test]
+fn test_last_friend_token_not_found() {
+    let node_report = empty_node_report(public_key(0));
+    match last_friend_token(&node_report, "alice") {
+        Err(InfoError::FriendNameNotFound(name)) => assert_eq!(name, "alice"),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+}
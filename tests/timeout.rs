@@ -0,0 +1,84 @@
+#![feature(futures_api, async_await, await_macro)]
+
+mod common;
+
+use std::time::Duration;
+
+use futures::future;
+use futures::executor::block_on;
+
+use stctrl::config::{config, ConfigError};
+use stctrl::info::{info, InfoError};
+use stctrl::profile::Profile;
+use stctrl::timeout::{with_timeout, Timer, TimeoutNode};
+
+use common::{MockNode, matches, public_key, empty_node_report, add_friend};
+
+fn node_with_friends() -> MockNode {
+    let mut node_report = empty_node_report(public_key(0));
+    add_friend(&mut node_report, "alice", public_key(1), 20);
+    MockNode::new(node_report)
+}
+
+#[test]
+fn test_with_timeout() {
+    let timer = Timer::new();
+    let timeout = Duration::from_millis(10);
+    assert_eq!(block_on(with_timeout(&timer, future::ready(3u32), timeout)).unwrap(), 3);
+    assert!(block_on(with_timeout(&timer, future::pending::<()>(), timeout)).is_err());
+    // The timer keeps serving after earlier timeouts:
+    assert!(block_on(with_timeout(&timer, future::pending::<()>(), timeout)).is_err());
+}
+
+#[test]
+fn test_timeout_node_passes_through() {
+    let mut mock_node = node_with_friends();
+    let mut node = TimeoutNode::new(&mut mock_node, &Timer::new(), Some(Duration::from_secs(10)));
+
+    let info_args = matches(&["info", "friends"]);
+    let info_matches = info_args.subcommand_matches("info").unwrap();
+    block_on(info(info_matches, &Profile::default(), &mut node)).unwrap();
+
+    let config_args = matches(&["config", "disable-friend", "--name", "alice"]);
+    let config_matches = config_args.subcommand_matches("config").unwrap();
+    block_on(config(config_matches, &mut node)).unwrap();
+
+    assert!(!node.timed_out());
+    assert_eq!(mock_node.config_calls().len(), 1);
+}
+
+#[test]
+fn test_timeout_node_keeps_permissions() {
+    let mut mock_node = node_with_friends().without_config();
+    let mut node = TimeoutNode::new(&mut mock_node, &Timer::new(), None);
+
+    let matches = matches(&["config", "disable-friend", "--name", "alice"]);
+    let config_matches = matches.subcommand_matches("config").unwrap();
+    match block_on(config(config_matches, &mut node)) {
+        Err(ConfigError::NoPermissions) => {},
+        res => panic!("Unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn test_timeout_node_reports_timeout() {
+    let mut mock_node = node_with_friends().unresponsive();
+    let timeout = Duration::from_millis(10);
+    let mut node = TimeoutNode::new(&mut mock_node, &Timer::new(), Some(timeout));
+
+    let info_args = matches(&["info", "friends"]);
+    let info_matches = info_args.subcommand_matches("info").unwrap();
+    match block_on(info(info_matches, &Profile::default(), &mut node)) {
+        Err(InfoError::Timeout(t)) => assert_eq!(t, timeout),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+
+    let config_args = matches(&["config", "disable-friend", "--name", "alice"]);
+    let config_matches = config_args.subcommand_matches("config").unwrap();
+    match block_on(config(config_matches, &mut node)) {
+        Err(ConfigError::Timeout(t)) => assert_eq!(t, timeout),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+
+    assert!(node.timed_out());
+}